@@ -0,0 +1,381 @@
+use crate::queries;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+static DATA_EXT: &str = "/.local/share/canvas-tui/";
+static DEFAULT_TOKEN_ENV: &str = "CANVAS_API_TOKEN";
+static DEFAULT_PROFILE: &str = "default";
+static DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 30;
+static DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 4;
+static DEFAULT_STALE_AFTER_HOURS: i64 = 24;
+static DEFAULT_LOOKAHEAD_DAYS: i64 = 21;
+static DEFAULT_OVERDUE_DAYS: i64 = 7;
+// Extensions `config::File::with_name` tries, in the same order
+static CONFIG_EXTENSIONS: [&str; 7] = ["toml", "json", "yaml", "yml", "ini", "ron", "json5"];
+
+/// A single Canvas account: one instance, one token and its own data file
+#[derive(Clone, Debug)]
+pub struct Profile {
+    pub name: String,
+    pub base_url: String,
+    pub token_source: TokenSource,
+    /// Allowlist of courses, when unset the active courses of the current term are used
+    pub course_ids: Option<Vec<u32>>,
+    /// Denylist applied to discovered courses
+    pub exclude_course_ids: Option<Vec<u32>>,
+    pub data_path: String,
+    /// Credit hours by course id, courses without an entry don't count towards the GPA
+    pub credits: HashMap<u32, f64>,
+    /// GPA and credit hours of earlier terms, for the cumulative GPA
+    pub prior_gpa: Option<f64>,
+    pub prior_credits: f64,
+}
+
+#[derive(Clone, Debug)]
+pub enum TokenSource {
+    /// Name of an environment variable (usually set in ~/.config/canvas-tui/.env)
+    Env(String),
+    /// Token written directly in config.toml
+    Literal(String),
+}
+
+impl Profile {
+    pub fn token(&self) -> Result<String, Box<dyn Error>> {
+        match &self.token_source {
+            TokenSource::Env(var) => std::env::var(var).map_err(|_| {
+                format!("profile \"{}\": environment variable {} is not set", self.name, var).into()
+            }),
+            TokenSource::Literal(token) => Ok(token.clone()),
+        }
+    }
+
+    // Neither list has been configured yet, so the first-run course picker should be shown
+    pub fn needs_course_pick(&self) -> bool {
+        self.course_ids.is_none() && self.exclude_course_ids.is_none()
+    }
+
+    // SQLite database used instead of the data file when storage = "sqlite"
+    pub fn db_path(&self) -> String {
+        match self.data_path.strip_suffix(".json") {
+            Some(stem) => format!("{}.db", stem),
+            None => format!("{}.db", self.data_path),
+        }
+    }
+}
+
+/// Raw profile table as written in config.toml, every key is optional so that
+/// the top-level keys can be used as a fallback
+#[derive(Deserialize, Default)]
+struct ProfileConfig {
+    base_url: Option<String>,
+    token: Option<String>,
+    token_env: Option<String>,
+    course_ids: Option<Vec<u32>>,
+    exclude_course_ids: Option<Vec<u32>>,
+    data_file: Option<String>,
+    // Keyed by course id, TOML keys are always strings
+    credits: Option<HashMap<String, f64>>,
+    prior_gpa: Option<f64>,
+    prior_credits: Option<f64>,
+}
+
+/// Where a profile's data is kept
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum StorageKind {
+    /// A single JSON file, rewritten on every save
+    Json,
+    /// A SQLite database next to the JSON file, written incrementally
+    Sqlite,
+}
+
+/// Behaviour settings shared by every profile and handed to the UI
+#[derive(Clone)]
+pub struct Settings {
+    /// Never touch the network, only show what is stored in the data file
+    pub offline: bool,
+    /// Courses not synced within this long are flagged in the UI
+    pub stale_after: chrono::Duration,
+    pub storage: StorageKind,
+    /// How far ahead assignments are fetched
+    pub lookahead: chrono::Duration,
+    /// Keep assignments without a due date instead of dropping them
+    pub include_undated: bool,
+    /// How long unfinished work stays in the Overdue section before it's archived
+    pub overdue_for: chrono::Duration,
+}
+
+pub struct Config {
+    pub profiles: Vec<Profile>,
+    pub default_profile: String,
+    pub request_timeout: Duration,
+    pub max_concurrent_requests: usize,
+    pub settings: Settings,
+}
+
+impl Config {
+    pub fn load(settings: &::config::Config, home: &str) -> Result<Self, Box<dyn Error>> {
+        let data_dir = match settings.get_string("data_dir") {
+            Ok(v) => expand_home(&v, home),
+            Err(_) => format!("{}{}", home, DATA_EXT),
+        };
+        let data_dir = if data_dir.ends_with('/') { data_dir } else { data_dir + "/" };
+
+        // Top-level keys act as defaults for every profile and, without a
+        // [profiles] table, make up the single "default" profile
+        let top = ProfileConfig {
+            base_url: settings.get_string("base_url").ok(),
+            token: settings.get_string("token").ok(),
+            token_env: settings.get_string("token_env").ok(),
+            course_ids: settings.get::<Vec<u32>>("course_ids").ok(),
+            exclude_course_ids: settings.get::<Vec<u32>>("exclude_course_ids").ok(),
+            data_file: settings.get_string("data_file").ok(),
+            credits: settings.get::<HashMap<String, f64>>("credits").ok(),
+            prior_gpa: settings.get::<f64>("prior_gpa").ok(),
+            prior_credits: settings.get::<f64>("prior_credits").ok(),
+        };
+
+        let raw_profiles = settings
+            .get::<HashMap<String, ProfileConfig>>("profiles")
+            .unwrap_or_default();
+
+        let mut profiles = vec![];
+        if raw_profiles.is_empty() {
+            let data_file = top.data_file.clone().unwrap_or(format!("{}data.json", data_dir));
+            profiles.push(resolve_profile(DEFAULT_PROFILE, ProfileConfig::default(), &top, data_file, home)?);
+        } else {
+            let mut names = raw_profiles.keys().cloned().collect::<Vec<String>>();
+            names.sort();
+            let mut raw_profiles = raw_profiles;
+            for name in names {
+                let raw = raw_profiles.remove(&name).unwrap_or_default();
+                let data_file = raw.data_file.clone().unwrap_or(format!("{}{}.json", data_dir, name));
+                profiles.push(resolve_profile(&name, raw, &top, data_file, home)?);
+            }
+        }
+
+        let default_profile = match settings.get_string("default_profile") {
+            Ok(v) => v,
+            Err(_) => profiles[0].name.clone(),
+        };
+
+        let request_timeout = Duration::from_secs(
+            settings
+                .get::<u64>("request_timeout_secs")
+                .unwrap_or(DEFAULT_REQUEST_TIMEOUT_SECS),
+        );
+        let max_concurrent_requests = settings
+            .get::<usize>("max_concurrent_requests")
+            .unwrap_or(DEFAULT_MAX_CONCURRENT_REQUESTS);
+
+        let app_settings = Settings {
+            offline: settings.get::<bool>("offline").unwrap_or(false),
+            stale_after: chrono::Duration::hours(
+                settings
+                    .get::<i64>("stale_after_hours")
+                    .unwrap_or(DEFAULT_STALE_AFTER_HOURS),
+            ),
+            storage: match settings.get_string("storage").as_deref() {
+                Ok("json") | Err(_) => StorageKind::Json,
+                Ok("sqlite") => StorageKind::Sqlite,
+                Ok(other) => {
+                    return Err(format!("storage must be \"json\" or \"sqlite\", not \"{}\"", other).into())
+                }
+            },
+            lookahead: chrono::Duration::days(
+                settings.get::<i64>("lookahead_days").unwrap_or(DEFAULT_LOOKAHEAD_DAYS),
+            ),
+            include_undated: settings.get::<bool>("include_undated").unwrap_or(false),
+            overdue_for: chrono::Duration::days(
+                settings.get::<i64>("overdue_days").unwrap_or(DEFAULT_OVERDUE_DAYS),
+            ),
+        };
+
+        Ok(Self {
+            profiles,
+            default_profile,
+            request_timeout,
+            max_concurrent_requests,
+            settings: app_settings,
+        })
+    }
+
+    pub fn profile_index(&self, name: &str) -> Result<usize, Box<dyn Error>> {
+        self.profiles.iter().position(|p| p.name == name).ok_or_else(|| {
+            let names = self.profiles.iter().map(|p| p.name.as_str()).collect::<Vec<&str>>();
+            format!("unknown profile \"{}\", available profiles: {}", name, names.join(", ")).into()
+        })
+    }
+}
+
+fn resolve_profile(
+    name: &str,
+    raw: ProfileConfig,
+    top: &ProfileConfig,
+    data_file: String,
+    home: &str,
+) -> Result<Profile, Box<dyn Error>> {
+    let base_url = match raw.base_url.as_ref().or(top.base_url.as_ref()) {
+        Some(v) => queries::parse_base_url(v).map_err(|e| format!("profile \"{}\": {}", name, e))?,
+        None => {
+            return Err(format!(
+                "profile \"{}\": base_url is not set in config.toml, e.g. base_url = \"https://canvas.example.edu\"",
+                name
+            )
+            .into())
+        }
+    };
+
+    let token_source = if let Some(token) = raw.token {
+        TokenSource::Literal(token)
+    } else if let Some(var) = raw.token_env {
+        TokenSource::Env(var)
+    } else if let Some(token) = top.token.clone() {
+        TokenSource::Literal(token)
+    } else {
+        TokenSource::Env(top.token_env.clone().unwrap_or(DEFAULT_TOKEN_ENV.to_string()))
+    };
+
+    let mut credits = HashMap::new();
+    for (course_id, hours) in raw.credits.as_ref().or(top.credits.as_ref()).into_iter().flatten() {
+        let course_id = course_id
+            .parse::<u32>()
+            .map_err(|_| format!("profile \"{}\": credits key \"{}\" is not a course id", name, course_id))?;
+        credits.insert(course_id, *hours);
+    }
+
+    Ok(Profile {
+        name: name.to_string(),
+        base_url,
+        token_source,
+        course_ids: raw.course_ids.or(top.course_ids.clone()),
+        exclude_course_ids: raw.exclude_course_ids.or(top.exclude_course_ids.clone()),
+        data_path: expand_home(&data_file, home),
+        credits,
+        prior_gpa: raw.prior_gpa.or(top.prior_gpa),
+        prior_credits: raw.prior_credits.or(top.prior_credits).unwrap_or(0.0),
+    })
+}
+
+// The config file for `stem` (a path without extension), picked the way `config::File::with_name` picks it
+pub fn find_config_file(stem: &str) -> Option<PathBuf> {
+    CONFIG_EXTENSIONS
+        .iter()
+        .map(|ext| PathBuf::from(format!("{}.{}", stem, ext)))
+        .find(|path| path.is_file())
+}
+
+// Write a course list (`course_ids` or `exclude_course_ids`) for a profile back into the config file it was
+// loaded from, keeping the rest of the file intact
+pub fn write_course_list(
+    config_path: &Path,
+    profile_name: &str,
+    key: &str,
+    course_ids: &[u32],
+) -> Result<(), Box<dyn Error>> {
+    let contents = std::fs::read_to_string(config_path)?;
+    let contents = match config_path.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => {
+            let mut doc = contents.parse::<toml_edit::DocumentMut>()?;
+            let ids = course_ids.iter().map(|id| *id as i64).collect::<toml_edit::Array>();
+            let in_profiles_table = doc
+                .get("profiles")
+                .and_then(|p| p.get(profile_name))
+                .is_some();
+            if in_profiles_table {
+                doc["profiles"][profile_name][key] = toml_edit::value(ids);
+            } else {
+                doc[key] = toml_edit::value(ids);
+            }
+            doc.to_string()
+        }
+        Some("json") => {
+            let mut doc = serde_json::from_str::<serde_json::Value>(&contents)?;
+            let ids = serde_json::json!(course_ids);
+            match doc.get_mut("profiles").and_then(|p| p.get_mut(profile_name)) {
+                Some(profile) => profile[key] = ids,
+                None => doc[key] = ids,
+            }
+            serde_json::to_string_pretty(&doc)? + "\n"
+        }
+        _ => {
+            return Err(format!(
+                "cannot save the course selection to {}, set {} there by hand",
+                config_path.display(),
+                key
+            )
+            .into());
+        }
+    };
+
+    std::fs::write(config_path, contents)?;
+    Ok(())
+}
+
+fn expand_home(path: &str, home: &str) -> String {
+    if let Some(rest) = path.strip_prefix('~') {
+        format!("{}{}", home, rest)
+    } else {
+        path.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_config(ext: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("canvas-tui-{}.{}", uuid::Uuid::new_v4(), ext));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn finds_config_in_with_name_order() {
+        let dir = std::env::temp_dir().join(format!("canvas-tui-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir(&dir).unwrap();
+        let stem = dir.join("config").to_string_lossy().into_owned();
+        assert_eq!(find_config_file(&stem), None);
+
+        std::fs::write(dir.join("config.yaml"), "").unwrap();
+        assert_eq!(find_config_file(&stem), Some(dir.join("config.yaml")));
+        std::fs::write(dir.join("config.json"), "{}").unwrap();
+        assert_eq!(find_config_file(&stem), Some(dir.join("config.json")));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn writes_toml_profile_table() {
+        let path = temp_config("toml", "# keep me\nbase_url = \"x\"\n\n[profiles.ta]\ntoken_env = \"T\"\n");
+        write_course_list(&path, "ta", "course_ids", &[1, 2]).unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let doc = written.parse::<toml_edit::DocumentMut>().unwrap();
+        assert!(written.contains("# keep me"));
+        assert_eq!(doc["profiles"]["ta"]["course_ids"].to_string().trim(), "[1, 2]");
+        assert!(doc.get("course_ids").is_none());
+    }
+
+    #[test]
+    fn writes_json_top_level() {
+        let path = temp_config("json", r#"{"base_url": "x"}"#);
+        write_course_list(&path, "default", "exclude_course_ids", &[]).unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let doc = serde_json::from_str::<serde_json::Value>(&written).unwrap();
+        assert_eq!(doc["base_url"], "x");
+        assert_eq!(doc["exclude_course_ids"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn refuses_formats_it_cannot_edit() {
+        let path = temp_config("yaml", "base_url: x\n");
+        let res = write_course_list(&path, "default", "course_ids", &[1]);
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(res.is_err());
+        assert_eq!(written, "base_url: x\n");
+    }
+}
@@ -1,68 +1,107 @@
 use std::error::Error;
-use std::path::Path;
-use tokio;
 
+mod config;
 mod types;
 mod queries;
+mod storage;
 mod ui;
 
-use types::data::Data;
+use queries::client::CanvasClient;
 
-static DATA_EXT: &str = "/.local/share/canvas-tui/";
 static CONFIG_EXT: &str = "/.config/canvas-tui/";
 
-fn create_path(pathstr: &str) -> Result<(), Box<dyn Error>> {
-    let path = Path::new(pathstr);
-    if !path.exists() {
-        let parent = match path.parent() {
-            Some(p) => p,
-            None => return Err("Invalid path".into()),
-        };
-        std::fs::create_dir_all(parent)?;
-        std::fs::File::create(path)?;
+struct Args {
+    profile: Option<String>,
+    pick_courses: bool,
+    offline: bool,
+}
+
+fn parse_args() -> Result<Args, Box<dyn Error>> {
+    let mut args = Args { profile: None, pick_courses: false, offline: false };
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        if arg == "--profile" || arg == "-p" {
+            match iter.next() {
+                Some(v) => args.profile = Some(v),
+                None => return Err(format!("{} requires a profile name", arg).into()),
+            }
+        } else if let Some(v) = arg.strip_prefix("--profile=") {
+            args.profile = Some(v.to_string());
+        } else if arg == "--pick-courses" {
+            args.pick_courses = true;
+        } else if arg == "--offline" {
+            args.offline = true;
+        } else {
+            return Err(format!("unknown argument \"{}\"\nusage: canvas-tui [--profile <name>] [--pick-courses] [--offline]", arg).into());
+        }
     }
-    Ok(())
+    Ok(args)
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
+    let args = parse_args()?;
+
     // Load config
     let home = std::env::var("HOME")?;
-    let settings = config::Config::builder()
-        .add_source(config::File::with_name(format!("{}{}config", home, CONFIG_EXT).as_str()))
+    // Resolve the file up front so the course picker writes back to the same one
+    let config_stem = format!("{}{}config", home, CONFIG_EXT);
+    let config_path = config::find_config_file(&config_stem)
+        .ok_or_else(|| format!("configuration file not found, create {}.toml", config_stem))?;
+    let settings = ::config::Config::builder()
+        .add_source(::config::File::from(config_path.as_path()))
         .build()?;
+    let mut config = config::Config::load(&settings, &home)?;
+    config.settings.offline |= args.offline;
+    let offline = config.settings.offline;
+    let profile_name = args.profile.unwrap_or(config.default_profile.clone());
+    let profile_i = config.profile_index(&profile_name)?;
+    dotenv::from_filename(format!("{}{}.env", home, CONFIG_EXT))?;
 
-    let course_ids = settings
-        .get_array("course_ids")?
-        .iter()
-        .map(|v| v.clone().try_deserialize::<u32>())
-        .collect::<Result<Vec<u32>, config::ConfigError>>()?;
-
-    let data_dir = match settings.get_string("data_dir") {
-        Ok(v) => {
-            if v.starts_with('~') {
-                format!("{}{}", home, &v[1..])
-            } else {
-                v
-            }
-        },
-        Err(_) => format!("{}{}", home, DATA_EXT)
+    // One HTTP client for the whole session, switching profiles reuses its connection pool
+    let profile = &mut config.profiles[profile_i];
+    let token = match profile.token() {
+        Ok(token) => token,
+        // Offline the token is never sent, so don't require one
+        Err(_) if offline => String::new(),
+        Err(e) => return Err(e),
     };
+    let client = CanvasClient::new(
+        config.request_timeout,
+        config.max_concurrent_requests,
+        profile.base_url.clone(),
+        token,
+    )?;
 
+    // First run: let the user pick from their active courses and remember the choice
+    if !offline && (args.pick_courses || profile.needs_course_pick()) {
+        let now = chrono::Utc::now();
+        let courses = queries::courses::query_courses(&client)
+            .await?
+            .into_iter()
+            .filter(|c| c.is_current(now))
+            .collect::<Vec<_>>();
+        match ui::picker::pick_courses(&courses)? {
+            Some(ids) => {
+                config::write_course_list(&config_path, &profile.name, "course_ids", &ids)?;
+                profile.course_ids = Some(ids);
+            }
+            // Skipped on first run: keep discovering every course and stop asking. With nothing
+            // discovered yet there was nothing to skip, so ask again next time
+            None if profile.needs_course_pick() && !courses.is_empty() => {
+                config::write_course_list(&config_path, &profile.name, "exclude_course_ids", &[])?;
+                profile.exclude_course_ids = Some(vec![]);
+            }
+            None => (),
+        }
+    }
 
-    // Load data 
-    println!("{}", data_dir);
-    dotenv::from_filename(format!("{}{}.env", home, CONFIG_EXT))?;
-    let data_path = data_dir.to_string() + "data.json";
-    create_path(&data_path)?;
-
-    let data = match Data::deserialize_from_file(&data_path) {
-        Ok(data) => data,
-        Err(_) => Data::empty(),
-    };
+    // Load data
+    let mut storage = storage::open(&config.profiles[profile_i], config.settings.storage)?;
+    let (data, load_error) = storage.load()?;
 
     // Run
-    let res = ui::run(data_path, course_ids, data).await;
+    let res = ui::run(storage, config.profiles, profile_i, client, config.settings, data, load_error).await;
     match res {
         Ok(_) => (),
         Err(e) => eprintln!("Error: {}", e),
@@ -0,0 +1,81 @@
+use crate::queries::error::CanvasError;
+use crate::queries::client::{CanvasClient, Fetched};
+use crate::queries::pagination::get_paginated;
+use crate::types::announcement::Announcement;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+
+// How far back announcements are fetched
+const ANNOUNCEMENT_DAYS: i64 = 30;
+
+#[derive(Deserialize)]
+struct RawAnnouncement {
+    id: u64,
+    title: Option<String>,
+    message: Option<String>,
+    posted_at: Option<DateTime<Utc>>,
+    html_url: String,
+    // "course_123"
+    context_code: String,
+    read_state: Option<String>,
+    user_name: Option<String>,
+    author: Option<Author>,
+}
+
+#[derive(Deserialize)]
+struct Author {
+    display_name: Option<String>,
+}
+
+// Announcements of every course, one listing per course so a course that can't be read
+// (403, 404, ...) doesn't hold back the others
+pub async fn query_announcements(client: &CanvasClient, course_ids: &[u32]) -> Fetched<Announcement> {
+    client
+        .fetch_courses(course_ids, |client, course_id| async move {
+            query_course(&client, course_id).await
+        })
+        .await
+}
+
+async fn query_course(client: &CanvasClient, course_id: u32) -> Result<Vec<Announcement>, CanvasError> {
+    let start = (Utc::now() - Duration::days(ANNOUNCEMENT_DAYS)).format("%Y-%m-%d");
+    let url = format!(
+        "{}/announcements?context_codes[]=course_{}&start_date={}",
+        client.v1_url(),
+        course_id,
+        start
+    );
+    let res = get_paginated::<RawAnnouncement>(client, &url).await?;
+    Ok(res.into_iter().filter_map(parse_announcement).collect())
+}
+
+fn parse_announcement(raw: RawAnnouncement) -> Option<Announcement> {
+    let course_id = raw.context_code.strip_prefix("course_")?.parse::<u32>().ok()?;
+    let author = raw.author.and_then(|a| a.display_name).or(raw.user_name);
+    let mut announcement = Announcement {
+        id: raw.id.to_string(),
+        course_id,
+        title: raw.title.unwrap_or_default(),
+        message: raw.message.unwrap_or_default(),
+        author,
+        posted_at: raw.posted_at,
+        html_url: raw.html_url,
+        read: raw.read_state.as_deref() == Some("read"),
+        links: vec![],
+    };
+    announcement.populate_links();
+    Some(announcement)
+}
+
+// Mark an announcement read on Canvas, like opening it there would
+pub async fn mark_read(client: &CanvasClient, course_id: u32, id: &str) -> Result<(), CanvasError> {
+    let url = format!(
+        "{}/courses/{}/discussion_topics/{}/read",
+        client.v1_url(),
+        course_id,
+        id
+    );
+    client.send(client.put(&url).header(reqwest::header::CONTENT_LENGTH, 0)).await?;
+    Ok(())
+}
@@ -1,11 +1,14 @@
-use crate::queries::GRAPHQL_URL;
+use crate::queries::error::CanvasError;
+use crate::queries::client::{CanvasClient, Fetched};
+use crate::queries::pagination::{PageInfo, PAGE_SIZE, connection_page_info, paginate_graphql};
+use crate::queries::parse_time;
 use crate::types::assignment::Assignment;
+use crate::types::submission::SubmissionState;
 
-use graphql_client::{GraphQLQuery, Response};
-use reqwest;
-use std::error::Error;
+use graphql_client::GraphQLQuery;
 
 // Define the GraphQL query
+#[allow(clippy::upper_case_acronyms)]
 type URL = String;
 type DateTime = String;
 #[derive(GraphQLQuery)]
@@ -16,86 +19,253 @@ type DateTime = String;
 )]
 struct GetAssignments;
 
-async fn perform_queries(
-    course_ids: &Vec<u32>,
-) -> Result<Vec<get_assignments::ResponseData>, Box<dyn Error>> {
-    let mut responses: Vec<get_assignments::ResponseData> = vec![];
-    for course_id in course_ids {
+// Walk every page of a course's assignmentsConnection and merge the nodes into one response
+async fn query_course(
+    client: &CanvasClient,
+    course_id: u32,
+) -> Result<get_assignments::ResponseData, CanvasError> {
+    let pages = paginate_graphql(|after| async move {
         let variables = get_assignments::Variables {
             course_id: course_id.to_string(),
+            first: Some(PAGE_SIZE),
+            after,
         };
-        let response = perform_query(variables).await?;
-        responses.push(response);
+        let data = client.graphql::<GetAssignments>(variables).await?;
+        let info = page_info(&data);
+        Ok((data, info))
+    })
+    .await?;
+    merge_pages(pages)
+}
+
+// One response holding the nodes of every page. Each page's nodes are taken on their own, a
+// page without any doesn't hide the ones after it
+fn merge_pages(mut pages: Vec<get_assignments::ResponseData>) -> Result<get_assignments::ResponseData, CanvasError> {
+    let mut nodes = vec![];
+    for page in pages.iter_mut() {
+        let page_nodes = page
+            .course
+            .as_mut()
+            .and_then(|c| c.assignments_connection.as_mut())
+            .and_then(|c| c.nodes.take());
+        nodes.extend(page_nodes.into_iter().flatten());
     }
 
-    Ok(responses)
+    // The first page with a connection carries the merged nodes
+    let base = pages
+        .iter()
+        .position(|p| p.course.as_ref().is_some_and(|c| c.assignments_connection.is_some()))
+        .unwrap_or(0);
+    if base >= pages.len() {
+        return Err(CanvasError::MissingField("data"));
+    }
+    let mut merged = pages.swap_remove(base);
+    if let Some(connection) = merged.course.as_mut().and_then(|c| c.assignments_connection.as_mut()) {
+        connection.nodes = Some(nodes);
+    }
+    Ok(merged)
 }
 
-async fn perform_query(
-    variables: get_assignments::Variables,
-) -> Result<get_assignments::ResponseData, Box<dyn Error>> {
-    // this is the important line
-    let request_body = GetAssignments::build_query(variables);
-    let api_token = std::env::var("CANVAS_API_TOKEN")?;
-
-    let client = reqwest::Client::new();
-    let res = client
-        .post(GRAPHQL_URL)
-        .bearer_auth(api_token)
-        .json(&request_body)
-        .send()
-        .await?;
-    let response_body: Response<get_assignments::ResponseData> = res.json().await?;
-
-    match response_body.data {
-        Some(data) => Ok(data),
-        None => Err("No data found".into()),
-    }
+fn page_info(data: &get_assignments::ResponseData) -> PageInfo {
+    connection_page_info!(data.course.as_ref().and_then(|c| c.assignments_connection.as_ref()))
 }
 
+// Returns the assignments along with a warning for each one that had to be skipped
 fn parse_assignments(
-    responses: Vec<get_assignments::ResponseData>,
-) -> Result<Vec<Assignment>, Box<dyn Error>> {
+    course_id: u32,
+    response: get_assignments::ResponseData,
+    lookahead: chrono::Duration,
+    include_undated: bool,
+) -> Result<(Vec<Assignment>, Vec<String>), CanvasError> {
+    // A null course means it doesn't exist or the token isn't enrolled in it
+    let course = match response.course {
+        Some(c) => c,
+        None => return Err(CanvasError::MissingField("course")),
+    };
+
     let mut assignments: Vec<Assignment> = vec![];
+    let mut warnings: Vec<String> = vec![];
     let now = chrono::Utc::now();
-    for response in responses {
-        if let Some(course) = response.course {
-            // Iterate over assignments
-            for a in course.assignments_connection.unwrap().nodes.unwrap() {
-                let a = a.unwrap();
-                let completed = a.submissions_connection.unwrap().nodes.unwrap().len() != 0;
-                let assignment: Assignment = Assignment::new(
-                    a.name.clone().unwrap(),
-                    course.course_nickname.clone(),
-                    a.description.clone(),
-                    a.html_url.clone().unwrap(),
-                    a.due_at,
-                    course.name.clone(),
-                    completed,
-                    a.lock_info.unwrap().is_locked,
-                )?;
-                if let Some(due) = assignment.date {
-                    // If assignment due within 14 days, add to list
-                    if due > now && due < now + chrono::Duration::days(21) {
-                        assignments.push(assignment);
-                    }
-                }
+    let nodes = course
+        .assignments_connection
+        .and_then(|c| c.nodes)
+        .unwrap_or_default();
+    // Iterate over assignments, skipping the ones too incomplete to show
+    for a in nodes.into_iter().flatten() {
+        // Without a URL the assignment can't be opened or matched on refresh
+        let html_url = match a.html_url {
+            Some(url) => url,
+            None => {
+                warnings.push(format!("{}: skipped an assignment without a URL", course.name));
+                continue;
+            }
+        };
+        let submission = a
+            .submissions_connection
+            .and_then(|c| c.nodes)
+            .and_then(|n| n.into_iter().flatten().next());
+        // No lockInfo means nothing is restricting access
+        let locked = a.lock_info.is_some_and(|l| l.is_locked);
+        let name = a.name.unwrap_or_else(|| "(Untitled)".to_string());
+        let mut assignment = match Assignment::new(
+            a.id,
+            name.clone(),
+            course.course_nickname.clone(),
+            a.description,
+            html_url,
+            a.due_at,
+            course.name.clone(),
+            course_id,
+            locked,
+        ) {
+            Ok(assignment) => assignment,
+            Err(e) => {
+                warnings.push(format!("{}: skipped \"{}\", invalid due date ({})", course.name, name, e));
+                continue;
             }
-        } else {
-            return Err("Assignment parsing failed".into());
+        };
+        assignment.term = course.term.as_ref().and_then(|t| t.name.clone());
+        if let Some(s) = submission {
+            let state = match s.state {
+                get_assignments::SubmissionState::graded => "graded",
+                get_assignments::SubmissionState::submitted => "submitted",
+                get_assignments::SubmissionState::pending_review => "pending_review",
+                _ => "unsubmitted",
+            };
+            assignment.submission = SubmissionState::from_canvas(
+                state,
+                s.late.unwrap_or(false),
+                s.missing.unwrap_or(false),
+                s.excused.unwrap_or(false),
+                s.redo_request.unwrap_or(false),
+            );
+            assignment.submitted_at = parse_time(s.submitted_at);
+            assignment.attempt = Some(s.attempt);
+            assignment.score = s.score;
+            assignment.grade = s.grade;
+        }
+        assignment.points_possible = a.points_possible;
+        assignment.grading_type = a.grading_type.map(|t| grading_type(&t).to_string());
+        // Keep anything due within the lookahead window, past assignments end up in the archive
+        match assignment.date {
+            Some(due) if due < now + lookahead => assignments.push(assignment),
+            None if include_undated => assignments.push(assignment),
+            _ => (),
         }
     }
 
-    return Ok(assignments);
+    Ok((assignments, warnings))
+}
+
+fn grading_type(t: &get_assignments::GradingType) -> &str {
+    match t {
+        get_assignments::GradingType::points => "points",
+        get_assignments::GradingType::percent => "percent",
+        get_assignments::GradingType::letter_grade => "letter_grade",
+        get_assignments::GradingType::gpa_scale => "gpa_scale",
+        get_assignments::GradingType::pass_fail => "pass_fail",
+        get_assignments::GradingType::not_graded => "not_graded",
+        get_assignments::GradingType::Other(t) => t,
+    }
+}
+
+fn sort_assignments(assignments: &mut [Assignment]) {
+    assignments.sort_by_key(|a| a.date);
 }
 
-fn sort_assignments(assignments: &mut Vec<Assignment>) {
-    assignments.sort_by(|a, b| a.date.unwrap().cmp(&b.date.unwrap()));
+pub async fn query_assignments(
+    client: &CanvasClient,
+    course_ids: &[u32],
+    lookahead: chrono::Duration,
+    include_undated: bool,
+) -> Fetched<Assignment> {
+    let mut fetched = client
+        .fetch_courses(course_ids, |client, course_id| async move {
+            let response = query_course(&client, course_id).await?;
+            let (assignments, warnings) = parse_assignments(course_id, response, lookahead, include_undated)?;
+            for warning in warnings {
+                client.warn(warning);
+            }
+            Ok(assignments)
+        })
+        .await;
+    sort_assignments(&mut fetched.items);
+    fetched
 }
 
-pub async fn query_assignments(course_ids: &Vec<u32>) -> Result<Vec<Assignment>, Box<dyn Error>> {
-    let responses = perform_queries(course_ids).await?;
-    let mut assignments = parse_assignments(responses)?;
-    sort_assignments(&mut assignments);
-    Ok(assignments)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(course: serde_json::Value) -> get_assignments::ResponseData {
+        serde_json::from_value(serde_json::json!({ "course": course })).unwrap()
+    }
+
+    fn assignment(id: &str, lock_info: serde_json::Value) -> serde_json::Value {
+        serde_json::json!({
+            "_id": id,
+            "name": format!("Assignment {}", id),
+            "description": null,
+            "htmlUrl": format!("https://x/courses/1/assignments/{}", id),
+            "dueAt": "2020-01-01T12:00:00Z",
+            "pointsPossible": 10.0,
+            "gradingType": "points",
+            "submissionsConnection": null,
+            "lockInfo": lock_info,
+        })
+    }
+
+    fn course(nodes: Vec<serde_json::Value>) -> serde_json::Value {
+        serde_json::json!({
+            "name": "Biology",
+            "courseNickname": null,
+            "term": null,
+            "assignmentsConnection": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": nodes,
+            },
+        })
+    }
+
+    #[test]
+    fn merges_nodes_after_an_empty_first_page() {
+        let mut first = course(vec![]);
+        first["assignmentsConnection"]["nodes"] = serde_json::Value::Null;
+        let pages = vec![
+            response(first),
+            response(course(vec![assignment("1", serde_json::Value::Null)])),
+            response(course(vec![assignment("2", serde_json::Value::Null)])),
+        ];
+        let merged = merge_pages(pages).unwrap();
+        let nodes = merged.course.unwrap().assignments_connection.unwrap().nodes.unwrap();
+        let ids = nodes.into_iter().flatten().map(|a| a.id).collect::<Vec<String>>();
+        assert_eq!(ids, vec!["1", "2"]);
+    }
+
+    #[test]
+    fn null_lock_info_is_unlocked() {
+        let nodes = vec![
+            assignment("1", serde_json::Value::Null),
+            assignment("2", serde_json::json!({ "isLocked": true })),
+        ];
+        let (assignments, warnings) = parse_assignments(1, response(course(nodes)), chrono::Duration::days(7), false).unwrap();
+        assert!(warnings.is_empty());
+        assert!(!assignments.iter().find(|a| a.id == "1").unwrap().locked);
+        assert!(assignments.iter().find(|a| a.id == "2").unwrap().locked);
+    }
+
+    #[test]
+    fn null_course_is_missing_field() {
+        let res = parse_assignments(1, response(serde_json::Value::Null), chrono::Duration::days(7), false);
+        assert!(matches!(res, Err(CanvasError::MissingField("course"))));
+    }
+
+    #[test]
+    fn assignment_without_url_is_skipped() {
+        let mut node = assignment("1", serde_json::Value::Null);
+        node["htmlUrl"] = serde_json::Value::Null;
+        let (assignments, warnings) = parse_assignments(1, response(course(vec![node])), chrono::Duration::days(7), false).unwrap();
+        assert!(assignments.is_empty());
+        assert_eq!(warnings.len(), 1);
+    }
 }
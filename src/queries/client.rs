@@ -0,0 +1,302 @@
+use crate::queries::rate_limit::{self, RateLimit};
+use crate::queries::error::CanvasError;
+use crate::queries::{graphql_url, v1_url};
+
+use graphql_client::{GraphQLQuery, Response as GraphQLResponse};
+use serde::de::DeserializeOwned;
+use reqwest::{RequestBuilder, Response};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex as SyncMutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+/// Connection to one Canvas instance. Cloning is cheap and every clone shares the same
+/// connection pool, so a single client is created at startup and handed around
+#[derive(Clone)]
+pub struct CanvasClient {
+    http: reqwest::Client,
+    base_url: String,
+    token: String,
+    max_concurrency: usize,
+    rate_limit: Arc<SyncMutex<RateLimit>>,
+    warnings: Arc<SyncMutex<Vec<String>>>,
+}
+
+// Attempts after the first one for throttled, 5xx or dropped requests
+const MAX_RETRIES: u32 = 4;
+
+/// Results of a per-course fetch: everything that succeeded plus the courses that failed
+pub struct Fetched<T> {
+    pub items: Vec<T>,
+    pub failures: Vec<(u32, CanvasError)>,
+}
+
+impl CanvasClient {
+    pub fn new(
+        timeout: Duration,
+        max_concurrency: usize,
+        base_url: String,
+        token: String,
+    ) -> Result<Self, CanvasError> {
+        let http = reqwest::Client::builder()
+            .timeout(timeout)
+            .connect_timeout(timeout)
+            .pool_idle_timeout(Duration::from_secs(90))
+            .build()?;
+        Ok(Self {
+            http,
+            base_url,
+            token,
+            max_concurrency: max_concurrency.max(1),
+            rate_limit: Arc::default(),
+            warnings: Arc::default(),
+        })
+    }
+
+    // Same connection pool and settings, different instance and/or account
+    pub fn with_instance(&self, base_url: String, token: String) -> Self {
+        Self {
+            http: self.http.clone(),
+            base_url,
+            token,
+            max_concurrency: self.max_concurrency,
+            rate_limit: Arc::default(),
+            warnings: Arc::default(),
+        }
+    }
+
+    pub fn get(&self, url: &str) -> RequestBuilder {
+        self.http.get(url).bearer_auth(&self.token)
+    }
+
+    pub fn post(&self, url: &str) -> RequestBuilder {
+        self.http.post(url).bearer_auth(&self.token)
+    }
+
+    pub fn put(&self, url: &str) -> RequestBuilder {
+        self.http.put(url).bearer_auth(&self.token)
+    }
+
+    pub fn rate_limit(&self) -> RateLimit {
+        self.rate_limit.lock().map(|r| r.clone()).unwrap_or_default()
+    }
+
+    // Record a non-fatal problem for the UI to show in its log
+    pub fn warn(&self, message: String) {
+        if let Ok(mut w) = self.warnings.lock() {
+            w.push(message);
+        }
+    }
+
+    pub fn take_warnings(&self) -> Vec<String> {
+        self.warnings
+            .lock()
+            .map(|mut w| std::mem::take(&mut *w))
+            .unwrap_or_default()
+    }
+
+    fn with_rate_limit(&self, f: impl FnOnce(&mut RateLimit)) {
+        if let Ok(mut r) = self.rate_limit.lock() {
+            f(&mut r);
+        }
+    }
+
+    /// Send a request, every query goes through here. Waits out Canvas throttling, slows
+    /// down as the rate limit budget runs low and retries transient failures with
+    /// jittered exponential backoff. Non-success responses are returned as errors
+    pub async fn send(&self, request: RequestBuilder) -> Result<Response, CanvasError> {
+        let mut attempt = 0;
+        loop {
+            let delay = self.rate_limit().delay();
+            if !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+
+            let req = match request.try_clone() {
+                Some(r) => r,
+                None => return Err(CanvasError::Request("request body cannot be retried".to_string())),
+            };
+            let res = match req.send().await {
+                Ok(res) => res,
+                Err(e) if attempt < MAX_RETRIES && rate_limit::is_transient_error(&e) => {
+                    self.with_rate_limit(|r| r.retries += 1);
+                    self.warn(format!("Retrying request ({}/{}): {}", attempt + 1, MAX_RETRIES, e));
+                    tokio::time::sleep(rate_limit::backoff(attempt)).await;
+                    attempt += 1;
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            self.with_rate_limit(|r| r.update(res.headers()));
+            let status = res.status();
+            if status.is_success() {
+                return Ok(res);
+            }
+
+            let url = res.url().to_string();
+            let headers = res.headers().clone();
+            let body = res.text().await.unwrap_or_default();
+            let throttled = rate_limit::is_throttled(status, &headers, &body);
+            if attempt < MAX_RETRIES && (throttled || rate_limit::is_transient_status(status)) {
+                let wait = rate_limit::retry_after(&headers).unwrap_or(rate_limit::backoff(attempt));
+                self.warn(format!(
+                    "Retrying {} in {:.1}s ({}/{}): {}",
+                    url,
+                    wait.as_secs_f64(),
+                    attempt + 1,
+                    MAX_RETRIES,
+                    status
+                ));
+                self.with_rate_limit(|r| {
+                    r.retries += 1;
+                    if throttled {
+                        // Every task waits this out at the top of the loop
+                        r.throttled_until = Some(Instant::now() + wait);
+                    }
+                });
+                if !throttled {
+                    tokio::time::sleep(wait).await;
+                }
+                attempt += 1;
+                continue;
+            }
+
+            if throttled {
+                return Err(CanvasError::Throttled);
+            }
+            return Err(CanvasError::from_status(status, url));
+        }
+    }
+
+    pub fn graphql_url(&self) -> String {
+        graphql_url(&self.base_url)
+    }
+
+    pub fn v1_url(&self) -> String {
+        v1_url(&self.base_url)
+    }
+
+    /// POST a GraphQL query. Partial data is returned as is, an `errors` payload without
+    /// any data becomes `CanvasError::GraphQL`
+    pub async fn graphql<Q: GraphQLQuery>(
+        &self,
+        variables: Q::Variables,
+    ) -> Result<Q::ResponseData, CanvasError> {
+        let request_body = Q::build_query(variables);
+        let res = self
+            .send(self.post(&self.graphql_url()).json(&request_body))
+            .await?;
+        parse_graphql(&res.text().await?)
+    }
+
+    /// Run `fetch` for every course with at most `max_concurrency` in flight. A failing
+    /// course is reported in `failures` instead of aborting the others
+    pub async fn fetch_courses<T, F, Fut>(&self, course_ids: &[u32], fetch: F) -> Fetched<T>
+    where
+        T: Send + 'static,
+        F: Fn(CanvasClient, u32) -> Fut,
+        Fut: Future<Output = Result<Vec<T>, CanvasError>> + Send + 'static,
+    {
+        let semaphore = Arc::new(Semaphore::new(self.max_concurrency));
+        let mut set = JoinSet::new();
+        // A task that panicked only hands back its id, this maps it to its course
+        let mut tasks = HashMap::new();
+        for (i, course_id) in course_ids.iter().copied().enumerate() {
+            let fut = fetch(self.clone(), course_id);
+            let semaphore = Arc::clone(&semaphore);
+            let handle = set.spawn(async move {
+                let _permit = semaphore.acquire_owned().await;
+                fut.await
+            });
+            tasks.insert(handle.id(), (i, course_id));
+        }
+
+        let mut results = vec![];
+        while let Some(res) = set.join_next_with_id().await {
+            let (id, res) = match res {
+                Ok((id, r)) => (id, r),
+                Err(e) => (e.id(), Err(e.into())),
+            };
+            let (i, course_id) = tasks[&id];
+            results.push((i, course_id, res));
+        }
+        // Keep the configured course order regardless of completion order
+        results.sort_by_key(|r| r.0);
+
+        let mut fetched = Fetched {
+            items: vec![],
+            failures: vec![],
+        };
+        for (_, course_id, res) in results {
+            match res {
+                Ok(items) => fetched.items.extend(items),
+                Err(e) => fetched.failures.push((course_id, e)),
+            }
+        }
+        fetched
+    }
+}
+
+/// Parse a GraphQL response body. Partial data is returned as is, an `errors` payload
+/// without any data becomes `CanvasError::GraphQL`
+fn parse_graphql<T: DeserializeOwned>(body: &str) -> Result<T, CanvasError> {
+    let response_body: GraphQLResponse<T> = serde_json::from_str(body)?;
+
+    match (response_body.data, response_body.errors) {
+        (Some(data), _) => Ok(data),
+        (None, Some(errors)) if !errors.is_empty() => Err(CanvasError::GraphQL(
+            errors.into_iter().map(|e| e.message).collect(),
+        )),
+        (None, _) => Err(CanvasError::MissingField("data")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_payload_is_graphql_error() {
+        let body = r#"{"data": null, "errors": [{"message": "not found"}, {"message": "denied"}]}"#;
+        match parse_graphql::<serde_json::Value>(body) {
+            Err(CanvasError::GraphQL(errors)) => assert_eq!(errors, vec!["not found", "denied"]),
+            other => panic!("expected a GraphQL error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn partial_data_is_kept() {
+        let body = r#"{"data": {"course": null}, "errors": [{"message": "denied"}]}"#;
+        assert!(parse_graphql::<serde_json::Value>(body).is_ok());
+    }
+
+    #[test]
+    fn missing_data_is_missing_field() {
+        assert!(matches!(parse_graphql::<serde_json::Value>("{}"), Err(CanvasError::MissingField("data"))));
+    }
+
+    #[tokio::test]
+    async fn panicked_fetch_names_its_course() {
+        let client = CanvasClient::new(Duration::from_secs(1), 2, "https://x".to_string(), String::new()).unwrap();
+        let fetched = client
+            .fetch_courses(&[10, 20, 30], |_, course_id| async move {
+                if course_id == 20 {
+                    panic!("fetch failed");
+                }
+                Ok(vec![course_id])
+            })
+            .await;
+        assert_eq!(fetched.items, vec![10, 30]);
+        assert_eq!(fetched.failures.len(), 1);
+        assert_eq!(fetched.failures[0].0, 20);
+        assert!(matches!(fetched.failures[0].1, CanvasError::Task(_)));
+    }
+
+    #[test]
+    fn malformed_body_is_deserialize_error() {
+        assert!(matches!(parse_graphql::<serde_json::Value>("<html>"), Err(CanvasError::Deserialize(_))));
+    }
+}
@@ -0,0 +1,272 @@
+use crate::queries::error::CanvasError;
+use crate::queries::client::CanvasClient;
+use crate::queries::pagination::{PAGE_SIZE, connection_page_info, paginate_graphql};
+use crate::queries::{check_errors, parse_time};
+use crate::types::conversation::{Conversation, ConversationMessage, Recipient};
+
+use graphql_client::GraphQLQuery;
+use serde::Deserialize;
+
+type DateTime = String;
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/schema.json",
+    query_path = "src/graphql/get_conversations.graphql",
+    response_derives = "Debug"
+)]
+struct GetConversations;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/schema.json",
+    query_path = "src/graphql/get_conversation_messages.graphql",
+    response_derives = "Debug"
+)]
+struct GetConversationMessages;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/schema.json",
+    query_path = "src/graphql/get_recipients.graphql",
+    response_derives = "Debug"
+)]
+struct GetRecipients;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/schema.json",
+    query_path = "src/graphql/create_conversation.graphql",
+    response_derives = "Debug"
+)]
+struct CreateConversation;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/schema.json",
+    query_path = "src/graphql/add_conversation_message.graphql",
+    response_derives = "Debug"
+)]
+struct AddConversationMessage;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/schema.json",
+    query_path = "src/graphql/update_conversation_participants.graphql",
+    response_derives = "Debug"
+)]
+struct UpdateConversationParticipants;
+
+#[derive(Deserialize)]
+struct RawUser {
+    id: u64,
+}
+
+// The inbox hangs off the user, whose id GraphQL can't look up by itself
+pub async fn query_user_id(client: &CanvasClient) -> Result<String, CanvasError> {
+    let url = format!("{}/users/self", client.v1_url());
+    let user = client.send(client.get(&url)).await?.json::<RawUser>().await?;
+    Ok(user.id.to_string())
+}
+
+// Conversations in the inbox, most recently active first
+pub async fn query_conversations(client: &CanvasClient, user_id: &str) -> Result<Vec<Conversation>, CanvasError> {
+    let pages = paginate_graphql(|after| async move {
+        let variables = get_conversations::Variables {
+            user_id: user_id.to_string(),
+            first: Some(PAGE_SIZE),
+            after,
+        };
+        let data = client.graphql::<GetConversations>(variables).await?;
+        let connection = match data.legacy_node.as_ref() {
+            Some(get_conversations::GetConversationsLegacyNode::User(u)) => u.conversations_connection.as_ref(),
+            _ => None,
+        };
+        let info = connection_page_info!(connection);
+        Ok((data, info))
+    })
+    .await?;
+
+    let mut conversations = vec![];
+    for page in pages {
+        let user = match page.legacy_node {
+            Some(get_conversations::GetConversationsLegacyNode::User(u)) => u,
+            _ => return Err(CanvasError::MissingField("user")),
+        };
+        let nodes = user
+            .conversations_connection
+            .and_then(|c| c.nodes)
+            .unwrap_or_default();
+        for p in nodes.into_iter().flatten() {
+            let c = match p.conversation {
+                Some(c) => c,
+                None => continue,
+            };
+            let participants = c
+                .conversation_participants_connection
+                .and_then(|c| c.nodes)
+                .unwrap_or_default()
+                .into_iter()
+                .flatten()
+                .filter_map(|p| p.user)
+                .map(|u| Recipient {
+                    id: u.id,
+                    name: u.name.unwrap_or_default(),
+                    instructor: false,
+                })
+                .collect();
+            conversations.push(Conversation {
+                id: c.id,
+                subject: c.subject.filter(|s| !s.is_empty()).unwrap_or_else(|| "(No subject)".to_string()),
+                context_name: c.context_name,
+                updated_at: parse_time(c.updated_at),
+                unread: p.workflow_state == "unread",
+                can_reply: c.can_reply.unwrap_or(true),
+                participants,
+            });
+        }
+    }
+    conversations.sort_by_key(|c| std::cmp::Reverse(c.updated_at));
+    Ok(conversations)
+}
+
+// Messages of a conversation, oldest first
+pub async fn query_messages(client: &CanvasClient, conversation_id: &str) -> Result<Vec<ConversationMessage>, CanvasError> {
+    let pages = paginate_graphql(|after| async move {
+        let variables = get_conversation_messages::Variables {
+            conversation_id: conversation_id.to_string(),
+            first: Some(PAGE_SIZE),
+            after,
+        };
+        let data = client.graphql::<GetConversationMessages>(variables).await?;
+        let connection = match data.legacy_node.as_ref() {
+            Some(get_conversation_messages::GetConversationMessagesLegacyNode::Conversation(c)) => {
+                c.conversation_messages_connection.as_ref()
+            }
+            _ => None,
+        };
+        let info = connection_page_info!(connection);
+        Ok((data, info))
+    })
+    .await?;
+
+    let mut messages = vec![];
+    for page in pages {
+        let conversation = match page.legacy_node {
+            Some(get_conversation_messages::GetConversationMessagesLegacyNode::Conversation(c)) => c,
+            _ => return Err(CanvasError::MissingField("conversation")),
+        };
+        let nodes = conversation
+            .conversation_messages_connection
+            .and_then(|c| c.nodes)
+            .unwrap_or_default();
+        for m in nodes.into_iter().flatten() {
+            messages.push(ConversationMessage {
+                author: m.author.and_then(|a| a.name),
+                body: m.body,
+                created_at: parse_time(m.created_at),
+            });
+        }
+    }
+    // Canvas lists the newest message first
+    messages.sort_by_key(|m| m.created_at);
+    Ok(messages)
+}
+
+// Everyone the user can message in a course, with its teachers and TAs flagged
+pub async fn query_recipients(client: &CanvasClient, user_id: &str, course_id: u32) -> Result<Vec<Recipient>, CanvasError> {
+    let pages = paginate_graphql(|after| async move {
+        let variables = get_recipients::Variables {
+            user_id: user_id.to_string(),
+            context: Some(format!("course_{}", course_id)),
+            first: Some(PAGE_SIZE),
+            after,
+        };
+        let data = client.graphql::<GetRecipients>(variables).await?;
+        let connection = match data.legacy_node.as_ref() {
+            Some(get_recipients::GetRecipientsLegacyNode::User(u)) => {
+                u.recipients.as_ref().and_then(|r| r.users_connection.as_ref())
+            }
+            _ => None,
+        };
+        let info = connection_page_info!(connection);
+        Ok((data, info))
+    })
+    .await?;
+
+    let course_id = course_id.to_string();
+    let mut recipients = vec![];
+    for page in pages {
+        let user = match page.legacy_node {
+            Some(get_recipients::GetRecipientsLegacyNode::User(u)) => u,
+            _ => return Err(CanvasError::MissingField("user")),
+        };
+        let nodes = user
+            .recipients
+            .and_then(|r| r.users_connection)
+            .and_then(|c| c.nodes)
+            .unwrap_or_default();
+        for u in nodes.into_iter().flatten() {
+            let instructor = u
+                .common_courses_connection
+                .and_then(|c| c.nodes)
+                .unwrap_or_default()
+                .into_iter()
+                .flatten()
+                .any(|e| {
+                    e.course.is_some_and(|c| c.id == course_id)
+                        && matches!(
+                            e.type_,
+                            get_recipients::EnrollmentType::TeacherEnrollment | get_recipients::EnrollmentType::TaEnrollment
+                        )
+                });
+            recipients.push(Recipient {
+                id: u.id,
+                name: u.name,
+                instructor,
+            });
+        }
+    }
+    Ok(recipients)
+}
+
+// Start a conversation with `recipients` (user ids) in a course
+pub async fn create_conversation(
+    client: &CanvasClient,
+    course_id: u32,
+    recipients: Vec<String>,
+    subject: String,
+    body: String,
+) -> Result<(), CanvasError> {
+    let variables = create_conversation::Variables {
+        recipients,
+        subject: Some(subject),
+        body,
+        context_code: Some(format!("course_{}", course_id)),
+    };
+    let data = client.graphql::<CreateConversation>(variables).await?;
+    check_errors(data.create_conversation.and_then(|p| p.errors).into_iter().flatten().map(|e| e.message))
+}
+
+pub async fn add_message(
+    client: &CanvasClient,
+    conversation_id: &str,
+    recipients: Vec<String>,
+    body: String,
+) -> Result<(), CanvasError> {
+    let variables = add_conversation_message::Variables {
+        conversation_id: conversation_id.to_string(),
+        recipients,
+        body,
+    };
+    let data = client.graphql::<AddConversationMessage>(variables).await?;
+    check_errors(data.add_conversation_message.and_then(|p| p.errors).into_iter().flatten().map(|e| e.message))
+}
+
+pub async fn mark_read(client: &CanvasClient, conversation_id: &str) -> Result<(), CanvasError> {
+    let variables = update_conversation_participants::Variables {
+        ids: vec![conversation_id.to_string()],
+        workflow_state: Some("read".to_string()),
+    };
+    let data = client.graphql::<UpdateConversationParticipants>(variables).await?;
+    check_errors(data.update_conversation_participants.and_then(|p| p.errors).into_iter().flatten().map(|e| e.message))
+}
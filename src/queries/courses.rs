@@ -0,0 +1,79 @@
+use crate::queries::error::CanvasError;
+use crate::queries::client::CanvasClient;
+use crate::queries::pagination::get_paginated;
+use crate::types::course::Course;
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct RawCourse {
+    id: u32,
+    // Missing for courses the user can no longer access (access_restricted_by_date)
+    name: Option<String>,
+    // Only present when the user has set a nickname, in which case `name` is the nickname
+    original_name: Option<String>,
+    start_at: Option<DateTime<Utc>>,
+    end_at: Option<DateTime<Utc>>,
+    term: Option<Term>,
+}
+
+#[derive(Deserialize)]
+struct Term {
+    name: Option<String>,
+    start_at: Option<DateTime<Utc>>,
+    end_at: Option<DateTime<Utc>>,
+}
+
+// Query every course the user is actively enrolled in
+pub async fn query_courses(client: &CanvasClient) -> Result<Vec<Course>, CanvasError> {
+    let url = format!(
+        "{}/courses?enrollment_state=active&include[]=term",
+        client.v1_url()
+    );
+    let res = get_paginated::<RawCourse>(client, &url).await?;
+
+    Ok(res.into_iter().filter_map(parse_course).collect())
+}
+
+fn parse_course(raw: RawCourse) -> Option<Course> {
+    let name = raw.name?;
+    let (name, nickname) = match raw.original_name {
+        Some(original) => (original, Some(name)),
+        None => (name, None),
+    };
+
+    // Prefer the term's dates, the "Default Term" usually has none
+    let (term, term_start, term_end) = match raw.term {
+        Some(t) => (t.name, t.start_at, t.end_at),
+        None => (None, None, None),
+    };
+    let (start_at, end_at) = if term_start.is_some() || term_end.is_some() {
+        (term_start, term_end)
+    } else {
+        (raw.start_at, raw.end_at)
+    };
+
+    Some(Course {
+        id: raw.id,
+        name,
+        nickname,
+        term,
+        start_at,
+        end_at,
+    })
+}
+
+// Active courses in the current term, minus the ones in `exclude`
+pub async fn discover_course_ids(
+    client: &CanvasClient,
+    exclude: &[u32],
+) -> Result<Vec<u32>, CanvasError> {
+    let now = Utc::now();
+    let courses = query_courses(client).await?;
+    Ok(courses
+        .iter()
+        .filter(|c| c.is_current(now) && !exclude.contains(&c.id))
+        .map(|c| c.id)
+        .collect())
+}
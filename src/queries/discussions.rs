@@ -0,0 +1,162 @@
+use crate::queries::error::CanvasError;
+use crate::queries::client::{CanvasClient, Fetched};
+use crate::queries::pagination::{PAGE_SIZE, connection_page_info, paginate_graphql};
+use crate::queries::{check_errors, parse_time};
+use crate::types::discussion::{Discussion, DiscussionEntry};
+
+use graphql_client::GraphQLQuery;
+
+type DateTime = String;
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/schema.json",
+    query_path = "src/graphql/get_discussions.graphql",
+    response_derives = "Debug"
+)]
+struct GetDiscussions;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/schema.json",
+    query_path = "src/graphql/get_discussion_entries.graphql",
+    response_derives = "Debug"
+)]
+struct GetDiscussionEntries;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/schema.json",
+    query_path = "src/graphql/create_discussion_entry.graphql",
+    response_derives = "Debug"
+)]
+struct CreateDiscussionEntry;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/schema.json",
+    query_path = "src/graphql/update_discussion_entries_read_state.graphql",
+    response_derives = "Debug"
+)]
+struct UpdateDiscussionEntriesReadState;
+
+// Discussion topics of every course, announcements left out
+pub async fn query_discussions(client: &CanvasClient, course_ids: &[u32]) -> Fetched<Discussion> {
+    client
+        .fetch_courses(course_ids, |client, course_id| async move {
+            query_course(&client, course_id).await
+        })
+        .await
+}
+
+async fn query_course(client: &CanvasClient, course_id: u32) -> Result<Vec<Discussion>, CanvasError> {
+    let pages = paginate_graphql(|after| async move {
+        let variables = get_discussions::Variables {
+            course_id: course_id.to_string(),
+            first: Some(PAGE_SIZE),
+            after,
+        };
+        let data = client.graphql::<GetDiscussions>(variables).await?;
+        let connection = data.course.as_ref().and_then(|c| c.discussions_connection.as_ref());
+        let info = connection_page_info!(connection);
+        Ok((data, info))
+    })
+    .await?;
+
+    let mut discussions = vec![];
+    for page in pages {
+        // A null course means it doesn't exist or the token isn't enrolled in it
+        let course = match page.course {
+            Some(c) => c,
+            None => return Err(CanvasError::MissingField("course")),
+        };
+        let nodes = course
+            .discussions_connection
+            .and_then(|c| c.nodes)
+            .unwrap_or_default();
+        for d in nodes.into_iter().flatten().filter(|d| !d.is_announcement) {
+            let counts = d.entry_counts;
+            discussions.push(Discussion {
+                id: d.id,
+                course_id,
+                title: d.title.unwrap_or_else(|| "(Untitled)".to_string()),
+                message: d.message.unwrap_or_default(),
+                author: d.author.and_then(|a| a.name),
+                posted_at: parse_time(d.posted_at),
+                last_reply_at: parse_time(d.last_reply_at),
+                replies: counts.as_ref().map_or(0, |c| c.replies_count),
+                unread: counts.as_ref().map_or(0, |c| c.unread_count),
+                locked: d.locked,
+                assignment_id: d.assignment.map(|a| a.id),
+            });
+        }
+    }
+    Ok(discussions)
+}
+
+// Every reply of a topic, in the order they were posted
+pub async fn query_entries(client: &CanvasClient, topic_id: &str) -> Result<Vec<DiscussionEntry>, CanvasError> {
+    let pages = paginate_graphql(|after| async move {
+        let variables = get_discussion_entries::Variables {
+            topic_id: topic_id.to_string(),
+            first: Some(PAGE_SIZE),
+            after,
+        };
+        let data = client.graphql::<GetDiscussionEntries>(variables).await?;
+        let connection = match data.legacy_node.as_ref() {
+            Some(get_discussion_entries::GetDiscussionEntriesLegacyNode::Discussion(d)) => {
+                d.discussion_entries_connection.as_ref()
+            }
+            _ => None,
+        };
+        let info = connection_page_info!(connection);
+        Ok((data, info))
+    })
+    .await?;
+
+    let mut entries = vec![];
+    for page in pages {
+        let discussion = match page.legacy_node {
+            Some(get_discussion_entries::GetDiscussionEntriesLegacyNode::Discussion(d)) => d,
+            _ => return Err(CanvasError::MissingField("discussion")),
+        };
+        let nodes = discussion
+            .discussion_entries_connection
+            .and_then(|c| c.nodes)
+            .unwrap_or_default();
+        for e in nodes.into_iter().flatten() {
+            entries.push(DiscussionEntry {
+                id: e.id,
+                parent_id: e.parent_id,
+                author: e.author.and_then(|a| a.name),
+                message: e.message.unwrap_or_default(),
+                created_at: parse_time(e.created_at),
+                deleted: e.deleted.unwrap_or(false),
+                // Our own entries have no participant and count as read
+                read: e.entry_participant.is_none_or(|p| p.read),
+            });
+        }
+    }
+    Ok(entries)
+}
+
+// Post a reply to the topic, or to one of its entries. `message` is HTML
+pub async fn create_entry(
+    client: &CanvasClient,
+    topic_id: &str,
+    parent_id: Option<String>,
+    message: String,
+) -> Result<(), CanvasError> {
+    let variables = create_discussion_entry::Variables {
+        topic_id: topic_id.to_string(),
+        parent_id,
+        message,
+    };
+    let data = client.graphql::<CreateDiscussionEntry>(variables).await?;
+    check_errors(data.create_discussion_entry.and_then(|p| p.errors).into_iter().flatten().map(|e| e.message))
+}
+
+pub async fn mark_entries_read(client: &CanvasClient, ids: Vec<String>) -> Result<(), CanvasError> {
+    let variables = update_discussion_entries_read_state::Variables { ids };
+    let data = client.graphql::<UpdateDiscussionEntriesReadState>(variables).await?;
+    check_errors(data.update_discussion_entries_read_state.and_then(|p| p.errors).into_iter().flatten().map(|e| e.message))
+}
@@ -0,0 +1,130 @@
+use reqwest::StatusCode;
+
+/// Everything that can go wrong talking to Canvas
+#[derive(Debug)]
+pub enum CanvasError {
+    /// The token is missing, invalid or expired, or not allowed to see the resource
+    Auth(String),
+    /// The request never got a response: DNS, connection refused, timeout, ...
+    Network(reqwest::Error),
+    /// Canvas answered with an unexpected status
+    Http { status: StatusCode, url: String },
+    /// Canvas was still throttling requests after every retry
+    Throttled,
+    /// The GraphQL response carried an `errors` payload instead of data
+    GraphQL(Vec<String>),
+    /// A field the app cannot do without was null or absent
+    MissingField(&'static str),
+    /// The response body did not have the expected shape
+    Deserialize(String),
+    /// A course fetch task panicked or was cancelled
+    Task(String),
+    /// The request could not be sent as built
+    Request(String),
+    /// A listing still had more pages after `MAX_PAGES`, so the rest of it was not fetched
+    PageLimit(usize),
+    /// A listing pointed back at a page it already returned, so the rest of it was not fetched
+    PageLoop,
+}
+
+impl CanvasError {
+    // Map a failed response to the matching variant
+    pub fn from_status(status: StatusCode, url: String) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED => {
+                CanvasError::Auth("invalid or expired access token".to_string())
+            }
+            StatusCode::FORBIDDEN => {
+                CanvasError::Auth(format!("not authorized to access {}", url))
+            }
+            StatusCode::TOO_MANY_REQUESTS => CanvasError::Throttled,
+            _ => CanvasError::Http { status, url },
+        }
+    }
+}
+
+impl std::fmt::Display for CanvasError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CanvasError::Auth(msg) => write!(f, "authentication failed: {}", msg),
+            CanvasError::Network(e) => write!(f, "network error: {}", e),
+            CanvasError::Http { status, url } => write!(f, "{} from {}", status, url),
+            CanvasError::Throttled => write!(f, "rate limited by Canvas, try again later"),
+            CanvasError::GraphQL(errors) => write!(f, "GraphQL error: {}", errors.join("; ")),
+            CanvasError::MissingField(field) => write!(f, "missing field \"{}\" in response", field),
+            CanvasError::Deserialize(msg) => write!(f, "unexpected response: {}", msg),
+            CanvasError::Task(msg) => write!(f, "fetch task failed: {}", msg),
+            CanvasError::Request(msg) => write!(f, "invalid request: {}", msg),
+            CanvasError::PageLimit(pages) => write!(f, "listing has more than {} pages, the rest was not fetched", pages),
+            CanvasError::PageLoop => write!(f, "listing repeats a page it already returned, the rest was not fetched"),
+        }
+    }
+}
+
+impl std::error::Error for CanvasError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CanvasError::Network(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for CanvasError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_decode() {
+            CanvasError::Deserialize(e.to_string())
+        } else {
+            CanvasError::Network(e)
+        }
+    }
+}
+
+impl From<serde_json::Error> for CanvasError {
+    fn from(e: serde_json::Error) -> Self {
+        CanvasError::Deserialize(e.to_string())
+    }
+}
+
+impl From<tokio::task::JoinError> for CanvasError {
+    fn from(e: tokio::task::JoinError) -> Self {
+        CanvasError::Task(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unauthorized_is_auth() {
+        let e = CanvasError::from_status(StatusCode::UNAUTHORIZED, "https://x/api/v1/courses".to_string());
+        assert!(matches!(e, CanvasError::Auth(_)));
+    }
+
+    #[test]
+    fn forbidden_is_auth() {
+        match CanvasError::from_status(StatusCode::FORBIDDEN, "https://x/api/v1/courses".to_string()) {
+            CanvasError::Auth(msg) => assert!(msg.contains("https://x/api/v1/courses")),
+            other => panic!("expected an auth error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn too_many_requests_is_throttled() {
+        let e = CanvasError::from_status(StatusCode::TOO_MANY_REQUESTS, "https://x".to_string());
+        assert!(matches!(e, CanvasError::Throttled));
+    }
+
+    #[test]
+    fn other_status_is_http() {
+        let e = CanvasError::from_status(StatusCode::NOT_FOUND, "https://x".to_string());
+        assert!(matches!(e, CanvasError::Http { status: StatusCode::NOT_FOUND, .. }));
+    }
+
+    #[test]
+    fn malformed_json_is_deserialize() {
+        let e: CanvasError = serde_json::from_str::<Vec<u32>>("{").unwrap_err().into();
+        assert!(matches!(e, CanvasError::Deserialize(_)));
+    }
+}
@@ -1,8 +1,8 @@
-use crate::queries::V1_URL;
-use crate::types::grade::Grade;
+use crate::queries::error::CanvasError;
+use crate::queries::client::{CanvasClient, Fetched};
+use crate::queries::pagination::get_paginated;
+use crate::types::grade::{AssignmentGroup, Cutoff, DEFAULT_SCHEME, Grade, GroupAssignment};
 
-use reqwest;
-use std::error::Error;
 use serde::Deserialize;
 
 #[derive(Deserialize)]
@@ -13,61 +13,181 @@ struct Enrollment {
 #[derive(Deserialize)]
 struct Grades {
     current_score: Option<f64>,
+    current_grade: Option<String>,
+    final_grade: Option<String>,
 }
 
-pub async fn query_grades(course_ids: &Vec<u32>) -> Result<Vec<Grade>, Box<dyn Error>> {
-    let mut responses: Vec<Grade> = vec![];
-    for course_id in course_ids {
-        let response = perform_query(course_id).await?;
-        match response {
-            Some(r) => responses.push(r),
-            None => (),
+#[derive(Deserialize)]
+struct Course {
+    name: Option<String>,
+    apply_assignment_group_weights: Option<bool>,
+    grading_standard_id: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct GradingStandard {
+    grading_scheme: Vec<SchemeEntry>,
+}
+
+#[derive(Deserialize)]
+struct SchemeEntry {
+    name: String,
+    // Fraction of the total, not a percentage
+    value: f64,
+}
+
+#[derive(Deserialize)]
+struct RawGroup {
+    id: u32,
+    name: String,
+    group_weight: Option<f64>,
+    rules: Option<Rules>,
+    #[serde(default)]
+    assignments: Vec<RawAssignment>,
+}
+
+#[derive(Deserialize)]
+struct Rules {
+    drop_lowest: Option<usize>,
+    drop_highest: Option<usize>,
+    never_drop: Option<Vec<u64>>,
+}
+
+#[derive(Deserialize)]
+struct RawAssignment {
+    id: u64,
+    name: String,
+    points_possible: Option<f64>,
+    #[serde(default)]
+    omit_from_final_grade: bool,
+    submission: Option<Submission>,
+}
+
+#[derive(Deserialize)]
+struct Submission {
+    score: Option<f64>,
+    excused: Option<bool>,
+}
+
+impl From<RawGroup> for AssignmentGroup {
+    fn from(raw: RawGroup) -> Self {
+        let rules = raw.rules.unwrap_or(Rules {
+            drop_lowest: None,
+            drop_highest: None,
+            never_drop: None,
+        });
+        // Excused and omitted assignments don't count towards the grade at all
+        let assignments = raw
+            .assignments
+            .into_iter()
+            .filter(|a| !a.omit_from_final_grade)
+            .filter(|a| a.submission.as_ref().is_none_or(|s| s.excused != Some(true)))
+            .map(|a| GroupAssignment {
+                id: a.id.to_string(),
+                name: a.name,
+                score: a.submission.and_then(|s| s.score),
+                points_possible: a.points_possible.unwrap_or(0.0),
+            })
+            .collect();
+        Self {
+            id: raw.id,
+            name: raw.name,
+            weight: raw.group_weight.unwrap_or(0.0),
+            drop_lowest: rules.drop_lowest.unwrap_or(0),
+            drop_highest: rules.drop_highest.unwrap_or(0),
+            never_drop: rules.never_drop.unwrap_or_default().iter().map(|id| id.to_string()).collect(),
+            assignments,
         }
     }
-
-    Ok(responses)
 }
 
-async fn perform_query(course_id: &u32) -> Result<Option<Grade>, Box<dyn Error>> {
-    let api_token = std::env::var("CANVAS_API_TOKEN")?;
-    let client = reqwest::Client::new();
+pub async fn query_grades(client: &CanvasClient, course_ids: &[u32]) -> Fetched<Grade> {
+    client
+        .fetch_courses(course_ids, |client, course_id| async move {
+            let response = perform_query(&client, course_id).await?;
+            Ok(response.into_iter().collect())
+        })
+        .await
+}
 
+async fn perform_query(client: &CanvasClient, course_id: u32) -> Result<Option<Grade>, CanvasError> {
     let grade_url = format!(
         "{}/courses/{}/enrollments?user_id=self&include[]=total_scores",
-        V1_URL,
+        client.v1_url(),
         course_id
     );
-    let grade_res = client
-        .get(&grade_url)
-        .bearer_auth(&api_token)
-        .send()
-        .await?
-        .json::<Vec<Enrollment>>()
-        .await?;
-    let grade = match grade_res.first() {
-        Some(e) => e.grades.current_score,
-        None => None, 
+    let grade_res = get_paginated::<Enrollment>(client, &grade_url).await?;
+    let (grade, current_grade, final_grade) = match grade_res.into_iter().next() {
+        Some(e) => (e.grades.current_score, e.grades.current_grade, e.grades.final_grade),
+        None => (None, None, None),
     };
 
     let course_url = format!(
         "{}/courses/{}",
-        V1_URL,
+        client.v1_url(),
         course_id
     );
     let course_res = client
-        .get(&course_url)
-        .bearer_auth(&api_token)
-        .send()
+        .send(client.get(&course_url))
         .await?
-        .text()
+        .json::<Course>()
         .await?;
+    let course = match course_res.name {
+        Some(name) => name,
+        None => return Err(CanvasError::MissingField("name")),
+    };
 
-    let v: serde_json::Value = serde_json::from_str(&course_res)?;
-    let course = v.get("name").unwrap().as_str().unwrap().to_string();
+    // Groups with their assignments and the user's submissions, for the grade breakdown
+    let groups_url = format!(
+        "{}/courses/{}/assignment_groups?include[]=assignments&include[]=submission",
+        client.v1_url(),
+        course_id
+    );
+    let groups = get_paginated::<RawGroup>(client, &groups_url).await?;
+
+    // A course without a scheme of its own uses Canvas's default one. When it has one the
+    // student can't see, only Canvas's letter grade is shown rather than guessed cutoffs
+    let scheme = match course_res.grading_standard_id {
+        Some(id) => query_scheme(client, course_id, id).await?,
+        None if current_grade.is_some() => DEFAULT_SCHEME
+            .iter()
+            .map(|(name, min)| Cutoff { name: name.to_string(), min: *min })
+            .collect(),
+        None => vec![],
+    };
 
-    Ok(match grade {
-        Some(g) => Some(Grade::new(course, None, g, *course_id)),
-        None => None,
-    })
+    Ok(grade.map(|g| {
+        let mut grade = Grade::new(course, None, g, course_id);
+        grade.weighted = course_res.apply_assignment_group_weights.unwrap_or(false);
+        grade.groups = groups.into_iter().map(AssignmentGroup::from).collect();
+        grade.current_grade = current_grade;
+        grade.final_grade = final_grade;
+        grade.scheme = scheme;
+        grade
+    }))
 
 }
+
+// The cutoffs of a grading standard, highest first. Standards set on the account often
+// aren't visible to students, those come back empty
+async fn query_scheme(client: &CanvasClient, course_id: u32, standard_id: u64) -> Result<Vec<Cutoff>, CanvasError> {
+    let url = format!(
+        "{}/courses/{}/grading_standards/{}",
+        client.v1_url(),
+        course_id,
+        standard_id
+    );
+    let standard = match client.send(client.get(&url)).await {
+        Ok(res) => res.json::<GradingStandard>().await?,
+        Err(CanvasError::Auth(_)) => return Ok(vec![]),
+        Err(CanvasError::Http { status, .. }) if status == reqwest::StatusCode::NOT_FOUND => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+    let mut scheme = standard
+        .grading_scheme
+        .into_iter()
+        .map(|e| Cutoff { name: e.name, min: e.value * 100.0 })
+        .collect::<Vec<Cutoff>>();
+    scheme.sort_by(|a, b| b.min.total_cmp(&a.min));
+    Ok(scheme)
+}
@@ -1,5 +1,86 @@
-pub const GRAPHQL_URL: &str = "https://elearning.mines.edu/api/graphql";
-pub const V1_URL: &str = "https://elearning.mines.edu/api/v1";
+use crate::queries::error::CanvasError;
 
+use chrono::Utc;
+use std::error::Error;
+
+pub mod announcements;
 pub mod assignments;
+pub mod client;
+pub mod conversations;
+pub mod courses;
+pub mod discussions;
+pub mod error;
 pub mod grades;
+pub mod pagination;
+pub mod rate_limit;
+
+/// Validate the `base_url` setting and normalize it to `scheme://host[:port][/path]`
+/// without a trailing slash, e.g. `https://canvas.instructure.com`.
+pub fn parse_base_url(base_url: &str) -> Result<String, Box<dyn Error>> {
+    let url = reqwest::Url::parse(base_url.trim()).map_err(|e| {
+        format!(
+            "invalid base_url \"{}\" in config.toml ({}), expected something like \"https://canvas.example.edu\"",
+            base_url, e
+        )
+    })?;
+    if url.scheme() != "https" && url.scheme() != "http" {
+        return Err(format!(
+            "invalid base_url \"{}\" in config.toml: scheme must be http or https",
+            base_url
+        )
+        .into());
+    }
+    if url.host_str().is_none() {
+        return Err(format!("invalid base_url \"{}\" in config.toml: missing host", base_url).into());
+    }
+    if url.query().is_some() || url.fragment().is_some() {
+        return Err(format!(
+            "invalid base_url \"{}\" in config.toml: must not contain a query or fragment",
+            base_url
+        )
+        .into());
+    }
+    Ok(url.as_str().trim_end_matches('/').to_string())
+}
+
+/// Parse an optional GraphQL `DateTime`, dropping values that aren't RFC 3339
+pub fn parse_time(s: Option<String>) -> Option<chrono::DateTime<Utc>> {
+    s.and_then(|s| chrono::DateTime::parse_from_rfc3339(&s).ok())
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// Turn the `errors` messages of a mutation payload into an error, if there are any
+pub fn check_errors(messages: impl IntoIterator<Item = String>) -> Result<(), CanvasError> {
+    let errors = messages.into_iter().collect::<Vec<_>>();
+    if errors.is_empty() { Ok(()) } else { Err(CanvasError::GraphQL(errors)) }
+}
+
+pub fn graphql_url(base_url: &str) -> String {
+    format!("{}/api/graphql", base_url)
+}
+
+pub fn v1_url(base_url: &str) -> String {
+    format!("{}/api/v1", base_url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_time_drops_bad_values() {
+        let t = parse_time(Some("2025-03-01T12:00:00-05:00".to_string())).unwrap();
+        assert_eq!(t.to_rfc3339(), "2025-03-01T17:00:00+00:00");
+        assert_eq!(parse_time(Some("yesterday".to_string())), None);
+        assert_eq!(parse_time(None), None);
+    }
+
+    #[test]
+    fn check_errors_only_fails_on_messages() {
+        assert!(check_errors(Vec::new()).is_ok());
+        match check_errors(vec!["locked".to_string()]) {
+            Err(CanvasError::GraphQL(errors)) => assert_eq!(errors, vec!["locked"]),
+            other => panic!("expected a GraphQL error, got {:?}", other),
+        }
+    }
+}
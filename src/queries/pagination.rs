@@ -0,0 +1,256 @@
+use crate::queries::error::CanvasError;
+use crate::queries::client::CanvasClient;
+
+use reqwest::header::{HeaderMap, LINK};
+use serde::de::DeserializeOwned;
+use std::collections::HashSet;
+use std::future::Future;
+
+// Page size requested from both the REST and GraphQL APIs (Canvas caps REST pages at 100)
+pub const PAGE_SIZE: i64 = 100;
+
+// Upper bound on pages fetched for one listing, in case the server keeps handing out cursors.
+// Hitting it is an error rather than a silently truncated listing
+const MAX_PAGES: usize = 500;
+
+/// The parts of a GraphQL `pageInfo` needed to walk a connection
+pub struct PageInfo {
+    pub has_next_page: bool,
+    pub end_cursor: Option<String>,
+}
+
+/// `PageInfo` of a generated GraphQL connection, passed as an `Option<&Connection>`; a missing
+/// connection is the last page. Every query generates its own `pageInfo` type, so this is a macro
+macro_rules! connection_page_info {
+    ($connection:expr) => {{
+        let connection = $connection;
+        $crate::queries::pagination::PageInfo {
+            has_next_page: connection.is_some_and(|c| c.page_info.has_next_page),
+            end_cursor: connection.and_then(|c| c.page_info.end_cursor.clone()),
+        }
+    }};
+}
+pub(crate) use connection_page_info;
+
+/// Fetch every page of a GraphQL connection. `fetch` is called with the cursor to start
+/// after (None for the first page) and returns the page along with its `pageInfo`
+pub async fn paginate_graphql<P, F, Fut>(mut fetch: F) -> Result<Vec<P>, CanvasError>
+where
+    F: FnMut(Option<String>) -> Fut,
+    Fut: Future<Output = Result<(P, PageInfo), CanvasError>>,
+{
+    let mut pages = vec![];
+    let mut cursor: Option<String> = None;
+    let mut seen = HashSet::new();
+    loop {
+        let (page, info) = fetch(cursor.clone()).await?;
+        pages.push(page);
+
+        if !info.has_next_page {
+            break;
+        }
+        if pages.len() >= MAX_PAGES {
+            return Err(CanvasError::PageLimit(MAX_PAGES));
+        }
+        match info.end_cursor {
+            Some(next) if seen.insert(next.clone()) => cursor = Some(next),
+            // A missing or repeated cursor would loop forever, and stopping there would pass off
+            // part of the listing as all of it
+            _ => return Err(CanvasError::PageLoop),
+        }
+    }
+    Ok(pages)
+}
+
+/// GET a REST listing and follow its `Link: <...>; rel="next"` headers until the last page
+pub async fn get_paginated<T: DeserializeOwned>(
+    client: &CanvasClient,
+    url: &str,
+) -> Result<Vec<T>, CanvasError> {
+    let mut items = vec![];
+    let mut next = Some(with_page_size(url));
+    let mut visited = HashSet::new();
+    while let Some(url) = next {
+        let res = client.send(client.get(&url)).await?;
+        next = next_link(res.headers());
+        items.extend(res.json::<Vec<T>>().await?);
+        visited.insert(url);
+
+        if next.as_ref().is_some_and(|n| visited.contains(n)) {
+            return Err(CanvasError::PageLoop);
+        }
+        if next.is_some() && visited.len() >= MAX_PAGES {
+            return Err(CanvasError::PageLimit(MAX_PAGES));
+        }
+    }
+    Ok(items)
+}
+
+fn with_page_size(url: &str) -> String {
+    if url.contains("per_page=") {
+        url.to_string()
+    } else if url.contains('?') {
+        format!("{}&per_page={}", url, PAGE_SIZE)
+    } else {
+        format!("{}?per_page={}", url, PAGE_SIZE)
+    }
+}
+
+fn next_link(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(LINK)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .find_map(parse_next_link)
+}
+
+// Parse a Link header value such as
+// `<https://x/api/v1/courses?page=2>; rel="next", <https://x/api/v1/courses?page=5>; rel="last"`
+fn parse_next_link(header: &str) -> Option<String> {
+    header.split(',').find_map(|link| {
+        let mut parts = link.split(';');
+        let url = parts.next()?.trim().strip_prefix('<')?.strip_suffix('>')?;
+        let is_next = parts.any(|p| {
+            let p = p.trim();
+            p == "rel=\"next\"" || p == "rel=next"
+        });
+        if is_next { Some(url.to_string()) } else { None }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    // Serve canned REST pages on localhost. `?page=n` answers with `pages[n]`: its JSON body and
+    // the page its `Link` header points at as next, if any. Returns the listing's URL
+    async fn serve(pages: Vec<(&'static str, Option<usize>)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let url = format!("{}/items", base);
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = vec![];
+                let mut buf = [0; 1024];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    let n = socket.read(&mut buf).await.unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buf[..n]);
+                }
+                let request = String::from_utf8_lossy(&request);
+                let page = request
+                    .split_whitespace()
+                    .nth(1)
+                    .and_then(|path| path.split(['?', '&']).find_map(|p| p.strip_prefix("page=")))
+                    .map_or(0, |n| n.parse::<usize>().unwrap());
+                let (body, next) = pages[page];
+                let link = next.map_or(String::new(), |n| {
+                    format!("Link: <{}/items?page={}&per_page=100>; rel=\"next\"\r\n", base, n)
+                });
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+                    body.len(),
+                    link,
+                    body
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        url
+    }
+
+    fn client() -> CanvasClient {
+        CanvasClient::new(Duration::from_secs(5), 1, String::new(), String::new()).unwrap()
+    }
+
+    // Serve `pages` in order, each pointing at the next by its index
+    async fn fetch_pages(pages: usize) -> Result<Vec<usize>, CanvasError> {
+        paginate_graphql(|after| async move {
+            let i = after.map_or(0, |c| c.parse::<usize>().unwrap());
+            let info = PageInfo {
+                has_next_page: i + 1 < pages,
+                end_cursor: Some((i + 1).to_string()),
+            };
+            Ok((i, info))
+        })
+        .await
+    }
+
+    #[test]
+    fn parses_next_link() {
+        let header = "<https://x/api/v1/courses?page=1>; rel=\"current\", \
+            <https://x/api/v1/courses?page=2>; rel=\"next\", \
+            <https://x/api/v1/courses?page=5>; rel=\"last\"";
+        assert_eq!(parse_next_link(header).as_deref(), Some("https://x/api/v1/courses?page=2"));
+        assert_eq!(parse_next_link("<https://x/a?page=2>; rel=next").as_deref(), Some("https://x/a?page=2"));
+    }
+
+    #[test]
+    fn no_next_link_on_last_page() {
+        let header = "<https://x/api/v1/courses?page=1>; rel=\"first\", <https://x/api/v1/courses?page=5>; rel=\"last\"";
+        assert_eq!(parse_next_link(header), None);
+        assert_eq!(parse_next_link(""), None);
+    }
+
+    #[test]
+    fn adds_page_size() {
+        assert_eq!(with_page_size("https://x/a"), "https://x/a?per_page=100");
+        assert_eq!(with_page_size("https://x/a?b=1"), "https://x/a?b=1&per_page=100");
+        assert_eq!(with_page_size("https://x/a?per_page=10"), "https://x/a?per_page=10");
+    }
+
+    #[tokio::test]
+    async fn follows_every_page() {
+        assert_eq!(fetch_pages(3).await.unwrap(), vec![0, 1, 2]);
+        assert_eq!(fetch_pages(1).await.unwrap(), vec![0]);
+    }
+
+    #[tokio::test]
+    async fn stuck_cursor_is_an_error() {
+        let res = paginate_graphql(|_| async {
+            let info = PageInfo {
+                has_next_page: true,
+                end_cursor: Some("same".to_string()),
+            };
+            Ok(((), info))
+        })
+        .await;
+        assert!(matches!(res, Err(CanvasError::PageLoop)));
+
+        let res = paginate_graphql(|_| async {
+            let info = PageInfo {
+                has_next_page: true,
+                end_cursor: None,
+            };
+            Ok(((), info))
+        })
+        .await;
+        assert!(matches!(res, Err(CanvasError::PageLoop)));
+    }
+
+    #[tokio::test]
+    async fn follows_link_headers() {
+        let url = serve(vec![("[1, 2]", Some(1)), ("[3]", Some(2)), ("[4, 5]", None)]).await;
+        let items = get_paginated::<u32>(&client(), &url).await.unwrap();
+        assert_eq!(items, vec![1, 2, 3, 4, 5]);
+    }
+
+    #[tokio::test]
+    async fn repeated_link_is_an_error() {
+        let url = serve(vec![("[1]", Some(1)), ("[2]", Some(2)), ("[3]", Some(1))]).await;
+        let res = get_paginated::<u32>(&client(), &url).await;
+        assert!(matches!(res, Err(CanvasError::PageLoop)));
+    }
+
+    #[tokio::test]
+    async fn errors_past_page_limit() {
+        assert!(fetch_pages(MAX_PAGES).await.is_ok());
+        assert!(matches!(fetch_pages(MAX_PAGES + 1).await, Err(CanvasError::PageLimit(MAX_PAGES))));
+    }
+}
@@ -0,0 +1,167 @@
+use reqwest::StatusCode;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use std::collections::hash_map::RandomState;
+use std::error::Error;
+use std::hash::{BuildHasher, Hasher};
+use std::io::ErrorKind;
+use std::time::{Duration, Instant};
+
+// Canvas hands every token a bucket of ~700 units that refills over time
+const SLOW_DOWN_BELOW: f64 = 150.0;
+const MAX_SLOW_DOWN: Duration = Duration::from_secs(2);
+
+const BACKOFF_BASE: Duration = Duration::from_millis(500);
+const BACKOFF_CAP: Duration = Duration::from_secs(30);
+
+/// Last known state of the Canvas rate limit for the current token
+#[derive(Clone, Default)]
+pub struct RateLimit {
+    /// Value of the last `X-Rate-Limit-Remaining` header
+    pub remaining: Option<f64>,
+    /// Value of the last `X-Request-Cost` header
+    pub last_cost: Option<f64>,
+    /// Set while Canvas is throttling us (403/429), requests wait until then
+    pub throttled_until: Option<Instant>,
+    /// Total number of retried requests this session
+    pub retries: u32,
+}
+
+impl RateLimit {
+    pub fn update(&mut self, headers: &HeaderMap) {
+        if let Some(v) = header_f64(headers, "x-rate-limit-remaining") {
+            self.remaining = Some(v);
+        }
+        if let Some(v) = header_f64(headers, "x-request-cost") {
+            self.last_cost = Some(v);
+        }
+    }
+
+    pub fn is_throttled(&self) -> bool {
+        self.throttled_until.is_some_and(|t| t > Instant::now())
+    }
+
+    // Requests are being slowed down to stay under the limit
+    pub fn is_low(&self) -> bool {
+        self.remaining.is_some_and(|r| r < SLOW_DOWN_BELOW)
+    }
+
+    // How long to wait before sending the next request: the rest of a throttle window,
+    // or a delay that grows as the remaining budget approaches zero
+    pub fn delay(&self) -> Duration {
+        if let Some(until) = self.throttled_until {
+            let now = Instant::now();
+            if until > now {
+                return until - now;
+            }
+        }
+        match self.remaining {
+            Some(remaining) if remaining < SLOW_DOWN_BELOW => {
+                let pressure = 1.0 - (remaining.max(0.0) / SLOW_DOWN_BELOW);
+                MAX_SLOW_DOWN.mul_f64(pressure)
+            }
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+fn header_f64(headers: &HeaderMap, name: &str) -> Option<f64> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
+
+// Canvas signals throttling with 429, or with 403 and a "Rate Limit Exceeded" body
+pub fn is_throttled(status: StatusCode, headers: &HeaderMap, body: &str) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS
+        || (status == StatusCode::FORBIDDEN
+            && (body.contains("Rate Limit Exceeded")
+                || header_f64(headers, "x-rate-limit-remaining").is_some_and(|r| r <= 0.0)))
+}
+
+pub fn is_transient_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::REQUEST_TIMEOUT
+            | StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+// Timeouts, failed connects and connections dropped mid-request. Other request errors
+// (bad URL, redirect loop, TLS setup, ...) would fail the same way on every attempt
+pub fn is_transient_error(e: &reqwest::Error) -> bool {
+    e.is_timeout() || e.is_connect() || (e.is_request() && connection_dropped(e))
+}
+
+fn connection_dropped(e: &(dyn Error + 'static)) -> bool {
+    let mut source = e.source();
+    while let Some(err) = source {
+        if let Some(io) = err.downcast_ref::<std::io::Error>() {
+            return matches!(
+                io.kind(),
+                ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted | ErrorKind::BrokenPipe | ErrorKind::UnexpectedEof
+            );
+        }
+        source = err.source();
+    }
+    false
+}
+
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let secs = header_f64(headers, RETRY_AFTER.as_str())?;
+    Some(Duration::from_secs_f64(secs.clamp(0.0, BACKOFF_CAP.as_secs_f64())))
+}
+
+// Exponential backoff with "equal jitter": half of the window is fixed, the other half random
+pub fn backoff(attempt: u32) -> Duration {
+    let window = BACKOFF_BASE
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(BACKOFF_CAP);
+    let half = window / 2;
+    half + half.mul_f64(jitter())
+}
+
+// Random number in [0, 1) without pulling in an RNG crate. The randomness comes from
+// `RandomState` alone: its keys are seeded randomly and change on every `new()`, so
+// hashing nothing still gives a fresh value each call
+fn jitter() -> f64 {
+    let hasher = RandomState::new().build_hasher();
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt;
+
+    #[derive(Debug)]
+    struct Wrapped(std::io::Error);
+
+    impl fmt::Display for Wrapped {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "request failed")
+        }
+    }
+
+    impl Error for Wrapped {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            Some(&self.0)
+        }
+    }
+
+    #[test]
+    fn only_dropped_connections_are_transient() {
+        let reset = Wrapped(std::io::Error::from(ErrorKind::ConnectionReset));
+        assert!(connection_dropped(&reset));
+        let denied = Wrapped(std::io::Error::from(ErrorKind::PermissionDenied));
+        assert!(!connection_dropped(&denied));
+        assert!(!connection_dropped(&std::io::Error::from(ErrorKind::ConnectionReset)));
+    }
+
+    #[test]
+    fn jitter_varies_within_range() {
+        let values = (0..32).map(|_| jitter()).collect::<Vec<_>>();
+        assert!(values.iter().all(|j| (0.0..1.0).contains(j)));
+        assert!(values.windows(2).any(|w| w[0] != w[1]));
+    }
+}
@@ -0,0 +1,24 @@
+use std::error::Error;
+use crate::storage::Storage;
+use crate::types::data::Data;
+
+/// The whole of `Data` in one JSON file, see `Data::serialize_to_file`
+pub struct JsonStorage {
+    path: String,
+}
+
+impl JsonStorage {
+    pub fn new(path: &str) -> Self {
+        Self { path: path.to_string() }
+    }
+}
+
+impl Storage for JsonStorage {
+    fn load(&mut self) -> Result<(Data, Option<String>), Box<dyn Error>> {
+        Data::load(&self.path)
+    }
+
+    fn save(&mut self, data: &mut Data) -> Result<(), Box<dyn Error>> {
+        data.serialize_to_file(&self.path)
+    }
+}
@@ -0,0 +1,24 @@
+pub mod json;
+pub mod sqlite;
+
+use std::error::Error;
+use crate::config::{Profile, StorageKind};
+use crate::types::data::Data;
+
+/// Somewhere a profile's `Data` is loaded from and saved to
+pub trait Storage: Send {
+    /// Read the stored data, along with a message for the log if something had to be
+    /// recovered, reset or imported along the way
+    fn load(&mut self) -> Result<(Data, Option<String>), Box<dyn Error>>;
+
+    /// Persist `data`. Backends may merge in changes made by other instances first
+    fn save(&mut self, data: &mut Data) -> Result<(), Box<dyn Error>>;
+}
+
+/// Open the configured backend for a profile
+pub fn open(profile: &Profile, kind: StorageKind) -> Result<Box<dyn Storage>, Box<dyn Error>> {
+    Ok(match kind {
+        StorageKind::Json => Box::new(json::JsonStorage::new(&profile.data_path)),
+        StorageKind::Sqlite => Box::new(sqlite::SqliteStorage::open(&profile.db_path(), &profile.data_path)?),
+    })
+}
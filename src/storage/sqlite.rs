@@ -0,0 +1,718 @@
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{Connection, OptionalExtension, Transaction, params};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::path::Path;
+use crate::storage::Storage;
+use crate::types::announcement::Announcement;
+use crate::types::assignment::Assignment;
+use crate::types::data::{Data, TOMBSTONE_DAYS, Tombstone};
+use crate::types::grade::{AssignmentGroup, Cutoff, Grade, GradeSnapshot, GroupAssignment};
+use crate::types::link::Link;
+use crate::types::migrations;
+use crate::types::submission::SubmissionState;
+
+// SCHEMA[n] upgrades a database at user_version n to n + 1
+const SCHEMA: &[&str] = &[
+    "CREATE TABLE meta (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    CREATE TABLE assignments (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        description TEXT,
+        html_url TEXT NOT NULL,
+        date TEXT,
+        course TEXT NOT NULL,
+        course_id INTEGER,
+        completed INTEGER NOT NULL,
+        custom INTEGER NOT NULL,
+        modified INTEGER NOT NULL,
+        modified_at TEXT,
+        locked INTEGER NOT NULL
+    );
+    CREATE TABLE links (
+        assignment_id TEXT NOT NULL REFERENCES assignments(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        url TEXT NOT NULL,
+        title TEXT NOT NULL,
+        PRIMARY KEY (assignment_id, position)
+    );
+    CREATE TABLE grades (
+        course_id INTEGER PRIMARY KEY,
+        course TEXT NOT NULL,
+        grade REAL NOT NULL
+    );
+    CREATE TABLE course_sync (
+        course_id INTEGER PRIMARY KEY,
+        last_synced TEXT NOT NULL
+    );
+    CREATE TABLE tombstones (
+        id TEXT PRIMARY KEY,
+        at TEXT NOT NULL
+    );",
+    "ALTER TABLE assignments ADD COLUMN archived INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE assignments ADD COLUMN term TEXT;
+    ALTER TABLE assignments ADD COLUMN submission_status TEXT;
+    ALTER TABLE assignments ADD COLUMN score REAL;",
+    "ALTER TABLE assignments ADD COLUMN submitted_at TEXT;
+    ALTER TABLE assignments ADD COLUMN attempt INTEGER;
+    UPDATE assignments SET submission_status = CASE
+        WHEN submission_status IN ('submitted', 'graded', 'late', 'missing') THEN submission_status
+        WHEN submission_status = 'resubmitted' THEN 'submitted'
+        ELSE 'unsubmitted'
+    END;
+    UPDATE assignments SET completed = 0 WHERE modified = 0;",
+    "ALTER TABLE assignments ADD COLUMN grade TEXT;
+    ALTER TABLE assignments ADD COLUMN points_possible REAL;
+    ALTER TABLE assignments ADD COLUMN grading_type TEXT;",
+    "ALTER TABLE grades ADD COLUMN weighted INTEGER NOT NULL DEFAULT 0;
+    CREATE TABLE assignment_groups (
+        id INTEGER PRIMARY KEY,
+        course_id INTEGER NOT NULL REFERENCES grades(course_id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        weight REAL NOT NULL,
+        drop_lowest INTEGER NOT NULL,
+        drop_highest INTEGER NOT NULL,
+        never_drop TEXT NOT NULL
+    );
+    CREATE TABLE group_assignments (
+        group_id INTEGER NOT NULL REFERENCES assignment_groups(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        assignment_id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        score REAL,
+        points_possible REAL NOT NULL,
+        PRIMARY KEY (group_id, position)
+    );",
+    "ALTER TABLE grades ADD COLUMN current_grade TEXT;
+    ALTER TABLE grades ADD COLUMN final_grade TEXT;
+    CREATE TABLE grading_cutoffs (
+        course_id INTEGER NOT NULL REFERENCES grades(course_id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        min REAL NOT NULL,
+        PRIMARY KEY (course_id, position)
+    );",
+    "ALTER TABLE assignments ADD COLUMN new_grade INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE grades ADD COLUMN seen REAL;
+    UPDATE grades SET seen = grade;
+    CREATE TABLE grade_history (
+        course_id INTEGER NOT NULL REFERENCES grades(course_id) ON DELETE CASCADE,
+        at TEXT NOT NULL,
+        grade REAL NOT NULL,
+        PRIMARY KEY (course_id, at)
+    );",
+    "CREATE TABLE announcements (
+        id TEXT PRIMARY KEY,
+        course_id INTEGER NOT NULL,
+        title TEXT NOT NULL,
+        message TEXT NOT NULL,
+        author TEXT,
+        posted_at TEXT,
+        html_url TEXT NOT NULL,
+        read INTEGER NOT NULL
+    );",
+    // Grade history outlives the course's row in grades, a course missing from one fetch keeps it
+    "CREATE TABLE grade_history_new (
+        course_id INTEGER NOT NULL,
+        at TEXT NOT NULL,
+        grade REAL NOT NULL,
+        PRIMARY KEY (course_id, at)
+    );
+    INSERT INTO grade_history_new SELECT course_id, at, grade FROM grade_history;
+    DROP TABLE grade_history;
+    ALTER TABLE grade_history_new RENAME TO grade_history;",
+];
+
+// Serialized form of every assignment and grade as last read or written, to find what changed
+#[derive(Default)]
+struct Fingerprints {
+    assignments: HashMap<String, String>,
+    grades: HashMap<u32, String>,
+    announcements: HashMap<String, String>,
+}
+
+/// `Data` in a SQLite database. Each save is one transaction that merges in what other instances
+/// wrote, like the JSON backend does, then only touches the rows that differ from the database
+pub struct SqliteStorage {
+    conn: Connection,
+    // data.json to import from the first time the database is loaded
+    json_path: String,
+    // What this instance last loaded or saved, only those rows are ever deleted
+    saved: Fingerprints,
+}
+
+impl SqliteStorage {
+    pub fn open(path: &str, json_path: &str) -> Result<Self, Box<dyn Error>> {
+        if let Some(dir) = Path::new(path).parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut conn = Connection::open(path)?;
+        // Other instances may be writing at the same time
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "foreign_keys", true)?;
+
+        let version: usize = conn.pragma_query_value(None, "user_version", |r| r.get(0))?;
+        if version > SCHEMA.len() {
+            return Err(format!(
+                "{} is schema version {}, but this build only understands up to version {}",
+                path,
+                version,
+                SCHEMA.len()
+            )
+            .into());
+        }
+        let tx = conn.transaction()?;
+        for sql in &SCHEMA[version..] {
+            tx.execute_batch(sql)?;
+        }
+        tx.pragma_update(None, "user_version", SCHEMA.len())?;
+        tx.commit()?;
+
+        Ok(Self {
+            conn,
+            json_path: json_path.to_string(),
+            saved: Fingerprints::default(),
+        })
+    }
+
+    // One-time import of the JSON data file, if there is one
+    fn import(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        let imported = self
+            .conn
+            .query_row("SELECT value FROM meta WHERE key = 'imported'", [], |r| r.get::<_, String>(0))
+            .optional()?;
+        if imported.is_some() {
+            return Ok(None);
+        }
+
+        let has_json = std::fs::metadata(&self.json_path).is_ok_and(|m| m.len() > 0);
+        let mut message = None;
+        let tx = self.conn.transaction()?;
+        if has_json {
+            let (data, load_error) = Data::load(&self.json_path)?;
+            write(&tx, &data, &Fingerprints::default(), &Fingerprints::default())?;
+            message = Some(match load_error {
+                Some(e) => e,
+                None => format!("Imported {} assignments from {}", data.assignments.len(), self.json_path),
+            });
+        }
+        tx.execute(
+            "INSERT INTO meta (key, value) VALUES ('imported', ?1)",
+            params![Utc::now().to_rfc3339()],
+        )?;
+        tx.commit()?;
+        Ok(message)
+    }
+}
+
+// Everything in the database, also used inside a save's transaction to merge
+fn read(conn: &Connection) -> Result<Data, Box<dyn Error>> {
+    let mut links: HashMap<String, Vec<Link>> = HashMap::new();
+    let mut stmt = conn.prepare("SELECT assignment_id, url, title FROM links ORDER BY assignment_id, position")?;
+    let rows = stmt.query_map([], |r| Ok((r.get::<_, String>(0)?, r.get(1)?, r.get(2)?)))?;
+    for row in rows {
+        let (id, url, title) = row?;
+        links.entry(id).or_default().push(Link::new(url, title));
+    }
+
+    let mut assignments = vec![];
+    let mut archive = vec![];
+    let mut stmt = conn.prepare(
+        "SELECT id, name, description, html_url, date, course, course_id, completed, custom,
+            modified, modified_at, locked, archived, term, submission_status, score, submitted_at, attempt,
+            grade, points_possible, grading_type, new_grade
+        FROM assignments",
+    )?;
+    let mut rows = stmt.query([])?;
+    while let Some(r) = rows.next()? {
+        let id: String = r.get(0)?;
+        let date: Option<String> = r.get(4)?;
+        let modified_at: Option<String> = r.get(10)?;
+        let archived: bool = r.get(12)?;
+        let submission: String = r.get(14)?;
+        let submitted_at: Option<String> = r.get(16)?;
+        let list = if archived { &mut archive } else { &mut assignments };
+        list.push(Assignment {
+            links: links.remove(&id).unwrap_or_default(),
+            id,
+            name: r.get(1)?,
+            description: r.get(2)?,
+            html_url: r.get(3)?,
+            date: date.map(|d| DateTime::parse_from_rfc3339(&d)).transpose()?,
+            course: r.get(5)?,
+            course_id: r.get(6)?,
+            completed: r.get(7)?,
+            custom: r.get(8)?,
+            modified: r.get(9)?,
+            modified_at: modified_at.map(|d| parse_utc(&d)).transpose()?,
+            locked: r.get(11)?,
+            term: r.get(13)?,
+            submission: SubmissionState::parse(&submission).unwrap_or_default(),
+            submitted_at: submitted_at.map(|d| parse_utc(&d)).transpose()?,
+            attempt: r.get(17)?,
+            score: r.get(15)?,
+            grade: r.get(18)?,
+            points_possible: r.get(19)?,
+            grading_type: r.get(20)?,
+            new_grade: r.get(21)?,
+        });
+    }
+
+    let mut group_assignments: HashMap<u32, Vec<GroupAssignment>> = HashMap::new();
+    let mut stmt = conn.prepare(
+        "SELECT group_id, assignment_id, name, score, points_possible
+        FROM group_assignments ORDER BY group_id, position",
+    )?;
+    let rows = stmt.query_map([], |r| {
+        Ok((
+            r.get::<_, u32>(0)?,
+            GroupAssignment {
+                id: r.get(1)?,
+                name: r.get(2)?,
+                score: r.get(3)?,
+                points_possible: r.get(4)?,
+            },
+        ))
+    })?;
+    for row in rows {
+        let (group_id, a) = row?;
+        group_assignments.entry(group_id).or_default().push(a);
+    }
+
+    let mut groups: HashMap<u32, Vec<AssignmentGroup>> = HashMap::new();
+    let mut stmt = conn.prepare(
+        "SELECT course_id, id, name, weight, drop_lowest, drop_highest, never_drop
+        FROM assignment_groups ORDER BY course_id, position",
+    )?;
+    let mut rows = stmt.query([])?;
+    while let Some(r) = rows.next()? {
+        let course_id: u32 = r.get(0)?;
+        let id: u32 = r.get(1)?;
+        let never_drop: String = r.get(6)?;
+        groups.entry(course_id).or_default().push(AssignmentGroup {
+            id,
+            name: r.get(2)?,
+            weight: r.get(3)?,
+            drop_lowest: r.get(4)?,
+            drop_highest: r.get(5)?,
+            never_drop: never_drop.split(',').filter(|s| !s.is_empty()).map(str::to_string).collect(),
+            assignments: group_assignments.remove(&id).unwrap_or_default(),
+        });
+    }
+
+    let mut schemes: HashMap<u32, Vec<Cutoff>> = HashMap::new();
+    let mut stmt = conn.prepare("SELECT course_id, name, min FROM grading_cutoffs ORDER BY course_id, position")?;
+    let rows = stmt.query_map([], |r| {
+        Ok((r.get::<_, u32>(0)?, Cutoff { name: r.get(1)?, min: r.get(2)? }))
+    })?;
+    for row in rows {
+        let (course_id, cutoff) = row?;
+        schemes.entry(course_id).or_default().push(cutoff);
+    }
+
+    let mut history: HashMap<u32, Vec<GradeSnapshot>> = HashMap::new();
+    let mut stmt = conn.prepare("SELECT course_id, at, grade FROM grade_history ORDER BY course_id, at")?;
+    let rows = stmt.query_map([], |r| Ok((r.get::<_, u32>(0)?, r.get::<_, String>(1)?, r.get::<_, f64>(2)?)))?;
+    for row in rows {
+        let (course_id, at, grade) = row?;
+        history.entry(course_id).or_default().push(GradeSnapshot { at: parse_utc(&at)?, grade });
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT course, grade, course_id, weighted, current_grade, final_grade, seen FROM grades ORDER BY course",
+    )?;
+    let mut grades = stmt
+        .query_map([], |r| {
+            Ok(Grade {
+                course: r.get(0)?,
+                grade: r.get(1)?,
+                course_id: r.get(2)?,
+                weighted: r.get(3)?,
+                groups: vec![],
+                current_grade: r.get(4)?,
+                final_grade: r.get(5)?,
+                scheme: vec![],
+                history: vec![],
+                seen: r.get(6)?,
+            })
+        })?
+        .collect::<Result<Vec<Grade>, rusqlite::Error>>()?;
+    for g in grades.iter_mut() {
+        g.groups = groups.remove(&g.course_id).unwrap_or_default();
+        g.scheme = schemes.remove(&g.course_id).unwrap_or_default();
+        g.history = history.remove(&g.course_id).unwrap_or_default();
+    }
+
+    let mut last_synced = HashMap::new();
+    let mut stmt = conn.prepare("SELECT course_id, last_synced FROM course_sync")?;
+    let rows = stmt.query_map([], |r| Ok((r.get::<_, u32>(0)?, r.get::<_, String>(1)?)))?;
+    for row in rows {
+        let (course_id, at) = row?;
+        last_synced.insert(course_id, parse_utc(&at)?);
+    }
+
+    let mut deleted = vec![];
+    let mut stmt = conn.prepare("SELECT id, at FROM tombstones")?;
+    let rows = stmt.query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?)))?;
+    for row in rows {
+        let (id, at) = row?;
+        deleted.push(Tombstone { id, at: parse_utc(&at)? });
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT id, course_id, title, message, author, posted_at, html_url, read FROM announcements",
+    )?;
+    let mut announcements = vec![];
+    let mut rows = stmt.query([])?;
+    while let Some(r) = rows.next()? {
+        let posted_at: Option<String> = r.get(5)?;
+        let mut a = Announcement {
+            id: r.get(0)?,
+            course_id: r.get(1)?,
+            title: r.get(2)?,
+            message: r.get(3)?,
+            author: r.get(4)?,
+            posted_at: posted_at.map(|d| parse_utc(&d)).transpose()?,
+            html_url: r.get(6)?,
+            read: r.get(7)?,
+            links: vec![],
+        };
+        a.populate_links();
+        announcements.push(a);
+    }
+
+    let mut data = Data {
+        version: migrations::VERSION,
+        assignments,
+        grades,
+        last_synced,
+        deleted,
+        archive,
+        announcements,
+    };
+    data.sort_assignments();
+    data.sort_announcements();
+    Ok(data)
+}
+
+impl Storage for SqliteStorage {
+    fn load(&mut self) -> Result<(Data, Option<String>), Box<dyn Error>> {
+        let message = self.import()?;
+        let data = read(&self.conn)?;
+        self.saved = fingerprints(&data)?;
+        Ok((data, message))
+    }
+
+    fn save(&mut self, data: &mut Data) -> Result<(), Box<dyn Error>> {
+        let tx = self.conn.transaction()?;
+        let theirs = read(&tx)?;
+        let stored = fingerprints(&theirs)?;
+        data.merge(theirs);
+        let saved = write(&tx, data, &self.saved, &stored)?;
+        tx.commit()?;
+        self.saved = saved;
+        Ok(())
+    }
+}
+
+// Write whatever differs from `stored`, the rows currently in the database, and return the new
+// fingerprints. Rows missing from `data` are only deleted if this instance `saved` them before,
+// rows written by other instances are left alone
+fn write(
+    tx: &Transaction,
+    data: &Data,
+    saved: &Fingerprints,
+    stored: &Fingerprints,
+) -> Result<Fingerprints, Box<dyn Error>> {
+    let current = fingerprints(data)?;
+
+    // Assignments removed since the last save
+    let ids = all_assignments(data).map(|(a, _)| a.id.as_str()).collect::<HashSet<&str>>();
+    for id in saved.assignments.keys().filter(|id| !ids.contains(id.as_str())) {
+        tx.execute("DELETE FROM assignments WHERE id = ?1", params![id])?;
+    }
+
+    for (a, archived) in all_assignments(data) {
+        if stored.assignments.get(&a.id) == current.assignments.get(&a.id) {
+            continue;
+        }
+        // The newest completion toggle wins if another instance changed the same row
+        tx.execute(
+            "INSERT INTO assignments (id, name, description, html_url, date, course, course_id,
+                completed, custom, modified, modified_at, locked, archived, term, submission_status, score, submitted_at, attempt, grade, points_possible, grading_type, new_grade)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22)
+            ON CONFLICT (id) DO UPDATE SET
+                name = excluded.name,
+                description = excluded.description,
+                html_url = excluded.html_url,
+                date = excluded.date,
+                course = excluded.course,
+                course_id = excluded.course_id,
+                completed = CASE WHEN assignments.modified_at > excluded.modified_at
+                    THEN assignments.completed ELSE excluded.completed END,
+                custom = excluded.custom,
+                modified = CASE WHEN assignments.modified_at > excluded.modified_at
+                    THEN assignments.modified ELSE excluded.modified END,
+                modified_at = NULLIF(MAX(COALESCE(assignments.modified_at, ''), COALESCE(excluded.modified_at, '')), ''),
+                locked = excluded.locked,
+                archived = excluded.archived,
+                term = excluded.term,
+                submission_status = excluded.submission_status,
+                score = excluded.score,
+                submitted_at = excluded.submitted_at,
+                attempt = excluded.attempt,
+                grade = excluded.grade,
+                points_possible = excluded.points_possible,
+                grading_type = excluded.grading_type,
+                new_grade = excluded.new_grade",
+            params![
+                a.id,
+                a.name,
+                a.description,
+                a.html_url,
+                a.date.map(|d| d.to_rfc3339()),
+                a.course,
+                a.course_id,
+                a.completed,
+                a.custom,
+                a.modified,
+                a.modified_at.map(|d| d.to_rfc3339()),
+                a.locked,
+                archived,
+                a.term,
+                a.submission.as_str(),
+                a.score,
+                a.submitted_at.map(|d| d.to_rfc3339()),
+                a.attempt,
+                a.grade,
+                a.points_possible,
+                a.grading_type,
+                a.new_grade,
+            ],
+        )?;
+        tx.execute("DELETE FROM links WHERE assignment_id = ?1", params![a.id])?;
+        for (i, link) in a.links.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO links (assignment_id, position, url, title) VALUES (?1, ?2, ?3, ?4)",
+                params![a.id, i, link.url, link.title],
+            )?;
+        }
+    }
+
+    let course_ids = data.grades.iter().map(|g| g.course_id).collect::<HashSet<u32>>();
+    for course_id in saved.grades.keys().filter(|id| !course_ids.contains(id)) {
+        tx.execute("DELETE FROM grades WHERE course_id = ?1", params![course_id])?;
+    }
+    for g in &data.grades {
+        if stored.grades.get(&g.course_id) == current.grades.get(&g.course_id) {
+            continue;
+        }
+        tx.execute(
+            "INSERT INTO grades (course_id, course, grade, weighted, current_grade, final_grade, seen)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            ON CONFLICT (course_id) DO UPDATE SET
+                course = excluded.course,
+                grade = excluded.grade,
+                weighted = excluded.weighted,
+                current_grade = excluded.current_grade,
+                final_grade = excluded.final_grade,
+                seen = excluded.seen",
+            params![g.course_id, g.course, g.grade, g.weighted, g.current_grade, g.final_grade, g.seen],
+        )?;
+        // History only grows, snapshots from other instances are kept as well
+        for snapshot in &g.history {
+            tx.execute(
+                "INSERT OR IGNORE INTO grade_history (course_id, at, grade) VALUES (?1, ?2, ?3)",
+                params![g.course_id, snapshot.at.to_rfc3339(), snapshot.grade],
+            )?;
+        }
+        tx.execute("DELETE FROM grading_cutoffs WHERE course_id = ?1", params![g.course_id])?;
+        for (i, cutoff) in g.scheme.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO grading_cutoffs (course_id, position, name, min) VALUES (?1, ?2, ?3, ?4)",
+                params![g.course_id, i, cutoff.name, cutoff.min],
+            )?;
+        }
+        tx.execute("DELETE FROM assignment_groups WHERE course_id = ?1", params![g.course_id])?;
+        for (i, group) in g.groups.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO assignment_groups
+                    (id, course_id, position, name, weight, drop_lowest, drop_highest, never_drop)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    group.id,
+                    g.course_id,
+                    i,
+                    group.name,
+                    group.weight,
+                    group.drop_lowest,
+                    group.drop_highest,
+                    group.never_drop.join(","),
+                ],
+            )?;
+            for (j, a) in group.assignments.iter().enumerate() {
+                tx.execute(
+                    "INSERT INTO group_assignments (group_id, position, assignment_id, name, score, points_possible)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![group.id, j, a.id, a.name, a.score, a.points_possible],
+                )?;
+            }
+        }
+    }
+
+    for (course_id, at) in &data.last_synced {
+        tx.execute(
+            "INSERT INTO course_sync (course_id, last_synced) VALUES (?1, ?2)
+            ON CONFLICT (course_id) DO UPDATE SET last_synced = MAX(last_synced, excluded.last_synced)",
+            params![course_id, at.to_rfc3339()],
+        )?;
+    }
+
+    let ids = data.announcements.iter().map(|a| a.id.as_str()).collect::<HashSet<&str>>();
+    for id in saved.announcements.keys().filter(|id| !ids.contains(id.as_str())) {
+        tx.execute("DELETE FROM announcements WHERE id = ?1", params![id])?;
+    }
+    for a in &data.announcements {
+        if stored.announcements.get(&a.id) == current.announcements.get(&a.id) {
+            continue;
+        }
+        // Once read by any instance it stays read
+        tx.execute(
+            "INSERT INTO announcements (id, course_id, title, message, author, posted_at, html_url, read)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            ON CONFLICT (id) DO UPDATE SET
+                course_id = excluded.course_id,
+                title = excluded.title,
+                message = excluded.message,
+                author = excluded.author,
+                posted_at = excluded.posted_at,
+                html_url = excluded.html_url,
+                read = MAX(announcements.read, excluded.read)",
+            params![
+                a.id,
+                a.course_id,
+                a.title,
+                a.message,
+                a.author,
+                a.posted_at.map(|d| d.to_rfc3339()),
+                a.html_url,
+                a.read,
+            ],
+        )?;
+    }
+
+    // Deletions, including ones made by other instances, win over older changes
+    for t in &data.deleted {
+        tx.execute(
+            "INSERT OR IGNORE INTO tombstones (id, at) VALUES (?1, ?2)",
+            params![t.id, t.at.to_rfc3339()],
+        )?;
+    }
+    let cutoff = Utc::now() - Duration::days(TOMBSTONE_DAYS);
+    tx.execute("DELETE FROM tombstones WHERE at < ?1", params![cutoff.to_rfc3339()])?;
+    tx.execute(
+        "DELETE FROM assignments WHERE EXISTS (
+            SELECT 1 FROM tombstones t
+            WHERE t.id = assignments.id AND COALESCE(assignments.modified_at, '') <= t.at
+        )",
+        [],
+    )?;
+
+    Ok(current)
+}
+
+// Upcoming and archived assignments, with whether each is archived
+fn all_assignments(data: &Data) -> impl Iterator<Item = (&Assignment, bool)> {
+    data.assignments
+        .iter()
+        .map(|a| (a, false))
+        .chain(data.archive.iter().map(|a| (a, true)))
+}
+
+fn fingerprints(data: &Data) -> Result<Fingerprints, Box<dyn Error>> {
+    let mut fingerprints = Fingerprints::default();
+    for (a, archived) in all_assignments(data) {
+        fingerprints
+            .assignments
+            .insert(a.id.clone(), format!("{}{}", archived, serde_json::to_string(a)?));
+    }
+    for g in &data.grades {
+        fingerprints.grades.insert(g.course_id, serde_json::to_string(g)?);
+    }
+    for a in &data.announcements {
+        fingerprints.announcements.insert(a.id.clone(), serde_json::to_string(a)?);
+    }
+    Ok(fingerprints)
+}
+
+fn parse_utc(s: &str) -> Result<DateTime<Utc>, chrono::ParseError> {
+    Ok(DateTime::parse_from_rfc3339(s)?.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_db() -> String {
+        std::env::temp_dir()
+            .join(format!("canvas-tui-{}.db", uuid::Uuid::new_v4()))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn grade(course_id: u32, grade: f64) -> Grade {
+        let mut g = Grade::new(format!("Course {}", course_id), None, grade, course_id);
+        g.record(None, Utc::now());
+        g
+    }
+
+    #[test]
+    fn save_keeps_other_instances_courses() {
+        let path = temp_db();
+        let mut a = SqliteStorage::open(&path, "").unwrap();
+        let mut b = SqliteStorage::open(&path, "").unwrap();
+        let (mut data_a, _) = a.load().unwrap();
+        let (mut data_b, _) = b.load().unwrap();
+
+        data_a.grades = vec![grade(1, 90.0)];
+        a.save(&mut data_a).unwrap();
+        data_b.grades = vec![grade(2, 80.0)];
+        b.save(&mut data_b).unwrap();
+
+        // B merged in A's course instead of deleting it
+        assert_eq!(data_b.grades.iter().filter(|g| g.course_id == 1).count(), 1);
+        let (stored, _) = SqliteStorage::open(&path, "").unwrap().load().unwrap();
+        let mut ids = stored.grades.iter().map(|g| g.course_id).collect::<Vec<u32>>();
+        ids.sort();
+        assert_eq!(ids, vec![1, 2]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn dropped_course_keeps_its_history() {
+        let path = temp_db();
+        let mut storage = SqliteStorage::open(&path, "").unwrap();
+        let (mut data, _) = storage.load().unwrap();
+        data.grades = vec![grade(1, 90.0)];
+        storage.save(&mut data).unwrap();
+
+        // Only a row this instance saved before is deleted, its history stays behind
+        storage.conn.execute("DELETE FROM grades WHERE course_id = 1", []).unwrap();
+        let history: i64 = storage
+            .conn
+            .query_row("SELECT COUNT(*) FROM grade_history WHERE course_id = 1", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(history, 1);
+
+        data.grades = vec![grade(1, 85.0)];
+        storage.save(&mut data).unwrap();
+        let (stored, _) = storage.load().unwrap();
+        assert_eq!(stored.grades[0].history.len(), 2);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
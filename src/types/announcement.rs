@@ -0,0 +1,39 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::types::html;
+use crate::types::link::Link;
+
+/// A course announcement, a discussion topic Canvas flags as an announcement
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Announcement {
+    // Canvas topic id
+    pub id: String,
+    pub course_id: u32,
+    pub title: String,
+    // HTML body as Canvas returns it
+    pub message: String,
+    pub author: Option<String>,
+    pub posted_at: Option<DateTime<Utc>>,
+    pub html_url: String,
+    pub read: bool,
+    // Taken from `message`, see `populate_links`
+    #[serde(default)]
+    pub links: Vec<Link>,
+}
+
+impl Announcement {
+    pub fn populate_links(&mut self) {
+        self.links = html::links(&self.message);
+    }
+
+    pub fn text(&self) -> String {
+        html::to_text(&self.message)
+    }
+
+    // Canvas's read state is kept, unless this copy was read locally in the meantime
+    pub fn update_from(&mut self, fetched: Announcement) {
+        let read = self.read || fetched.read;
+        *self = fetched;
+        self.read = read;
+    }
+}
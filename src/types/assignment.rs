@@ -1,35 +1,66 @@
-use chrono::{DateTime, Duration, FixedOffset, TimeZone};
+use chrono::{DateTime, Duration, FixedOffset, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
-use std::error::Error;
+use crate::types::html;
 use crate::types::link::Link;
-use select::document::Document;
-use select::predicate::Name;
+use crate::types::submission::SubmissionState;
 
-#[derive(Hash, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct Assignment {
+    // Canvas `_id` for fetched assignments, a random UUID for custom ones
+    pub id: String,
     pub name: String,
     pub description: Option<String>,
     pub html_url: String,
     pub date: Option<DateTime<FixedOffset>>,
     pub course: String,
+    #[serde(default)]
+    pub course_id: Option<u32>,
+    // The user's own "done" toggle, independent of what Canvas says
     pub completed: bool,
     pub custom: bool,
     pub modified: bool,
+    // Last time the user changed this assignment (completion toggle, creation)
+    #[serde(default)]
+    pub modified_at: Option<DateTime<Utc>>,
     pub locked: bool,
     pub links: Vec<Link>,
+    // Term of the course, used to group the archive
+    #[serde(default)]
+    pub term: Option<String>,
+    #[serde(default)]
+    pub submission: SubmissionState,
+    #[serde(default)]
+    pub submitted_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub attempt: Option<i64>,
+    #[serde(default)]
+    pub score: Option<f64>,
+    // Grade as Canvas displays it, depends on the grading type (e.g. "A-", "complete", "95%")
+    #[serde(default)]
+    pub grade: Option<String>,
+    #[serde(default)]
+    pub points_possible: Option<f64>,
+    // Canvas GradingType: points, percent, letter_grade, gpa_scale, pass_fail or not_graded
+    #[serde(default)]
+    pub grading_type: Option<String>,
+    // Graded or regraded since the user last looked at it
+    #[serde(default)]
+    pub new_grade: bool,
 }
 
 impl Assignment {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
+        id: String,
         name: String,
         nickname: Option<String>,
         description: Option<String>,
         html_url: String,
         datestring: Option<String>,
         course: String,
-        completed: bool,
+        course_id: u32,
         locked: bool,
-    ) -> Result<Self, Box<dyn Error>> {
+    ) -> Result<Self, chrono::ParseError> {
         let course = if let Some(nickname) = nickname {
             nickname
         } else {
@@ -43,16 +74,28 @@ impl Assignment {
         };
 
         let mut a = Self {
+            id,
             name,
             description,
             html_url,
             date,
             course,
-            completed,
+            course_id: Some(course_id),
+            completed: false,
             custom: false,
             modified: false,
+            modified_at: None,
             locked,
             links: vec![],
+            term: None,
+            submission: SubmissionState::Unsubmitted,
+            submitted_at: None,
+            attempt: None,
+            score: None,
+            grade: None,
+            points_possible: None,
+            grading_type: None,
+            new_grade: false,
         };
         a.populate_links();
         Ok(a)
@@ -68,57 +111,125 @@ impl Assignment {
         
 
         Self {
+            id: uuid::Uuid::new_v4().to_string(),
             name: String::new(),
             description: None,
             html_url: String::new(),
             date: Some(dt),
             course: String::new(),
+            course_id: None,
             completed: false,
             custom: true,
             modified: false,
+            modified_at: Some(Utc::now()),
             locked: false,
-            links: vec![]
+            links: vec![],
+            term: None,
+            submission: SubmissionState::Unsubmitted,
+            submitted_at: None,
+            attempt: None,
+            score: None,
+            grade: None,
+            points_possible: None,
+            grading_type: None,
+            new_grade: false,
+        }
+    }
+
+    pub fn is_past(&self) -> bool {
+        self.date.is_some_and(|date| date <= Utc::now())
+    }
+
+    // Marked done by the user or turned in on Canvas
+    pub fn is_finished(&self) -> bool {
+        self.completed || self.submission.is_turned_in()
+    }
+
+    // Past due and not done yet
+    pub fn is_overdue(&self) -> bool {
+        self.is_past() && !self.is_finished()
+    }
+
+    // Take the Canvas side of a freshly fetched copy, keeping the user's own changes
+    pub fn update_from(&mut self, fetched: Assignment) {
+        // A score that appeared or changed is pointed out until the user looks at it
+        if fetched.score.is_some() && (fetched.score != self.score || fetched.grade != self.grade) {
+            self.new_grade = true;
         }
+        self.locked = fetched.locked;
+        self.description = fetched.description;
+        self.date = fetched.date;
+        self.course_id = fetched.course_id;
+        self.term = fetched.term;
+        self.submission = fetched.submission;
+        self.submitted_at = fetched.submitted_at;
+        self.attempt = fetched.attempt;
+        self.score = fetched.score;
+        self.grade = fetched.grade;
+        self.points_possible = fetched.points_possible;
+        self.grading_type = fetched.grading_type;
+        self.populate_links();
+    }
+
+    // Take another instance's copy: its user changes if they're newer, its Canvas data if
+    // that instance synced the course more recently
+    pub fn merge_from(&mut self, theirs: Assignment, newer_sync: bool) {
+        if theirs.modified_at > self.modified_at {
+            self.completed = theirs.completed;
+            self.modified = theirs.modified;
+            self.modified_at = theirs.modified_at;
+        }
+        if newer_sync {
+            self.name = theirs.name;
+            self.description = theirs.description;
+            self.date = theirs.date;
+            self.locked = theirs.locked;
+            self.links = theirs.links;
+            self.term = theirs.term;
+            self.submission = theirs.submission;
+            self.submitted_at = theirs.submitted_at;
+            self.attempt = theirs.attempt;
+            self.score = theirs.score;
+            self.grade = theirs.grade;
+            self.points_possible = theirs.points_possible;
+            self.grading_type = theirs.grading_type;
+            self.new_grade |= theirs.new_grade;
+        }
+    }
+
+    // "score / possible", or None if the assignment isn't worth points
+    pub fn score_text(&self) -> Option<String> {
+        let possible = self.points_possible?;
+        match self.score {
+            Some(score) => Some(format!("{} / {}", score, possible)),
+            None => Some(format!("- / {}", possible)),
+        }
+    }
+
+    pub fn touch(&mut self) {
+        self.modified_at = Some(Utc::now());
     }
 
     pub fn populate_links(&mut self) {
-        let description = match &self.description {
-            Some(v) => v,
-            None => return,
-        };
-        self.links = Document::from(description.as_str())
-            .find(Name("a"))
-            .map(|n| {
-                let url = match n.attr("href") {
-                    Some(v) => v,
-                    None => return None,
-                };
-                let text = n.text();
-                Some(Link::new(url.to_string(), text))
-            })
-            .filter_map(|x| x)
-            .collect::<Vec<Link>>();
+        if let Some(description) = &self.description {
+            self.links = html::links(description);
+        }
     }
 
     // Make the due date one day later
     pub fn increment_due_date(&mut self) {
-
-        match self.date {
-            Some(dt) => self.date = Some(dt + Duration::days(1)),
-            None => return,
+        if let Some(dt) = self.date {
+            self.date = Some(dt + Duration::days(1));
         }
     }
 
     // Make the due date one day earlier (bounded by today)
     pub fn decrement_due_date(&mut self) {
-        match self.date {
-            Some(dt) => {
-                let new_dt = dt - Duration::days(1);
-                if new_dt > chrono::Local::now() {
-                    self.date = Some(dt - Duration::days(1));
-                }
-            },
-            None => return,
+        if let Some(dt) = self.date {
+            let new_dt = dt - Duration::days(1);
+            if new_dt > chrono::Local::now() {
+                self.date = Some(dt - Duration::days(1));
+            }
         }
     }
 
@@ -0,0 +1,53 @@
+use chrono::{DateTime, Utc};
+
+/// A conversation in the Canvas inbox
+pub struct Conversation {
+    pub id: String,
+    pub subject: String,
+    // Course or group the conversation was started in
+    pub context_name: Option<String>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub unread: bool,
+    pub can_reply: bool,
+    // Everyone in the conversation, the user included
+    pub participants: Vec<Recipient>,
+}
+
+impl Conversation {
+    // Names of the participants other than the user
+    pub fn others(&self, user_id: &str) -> String {
+        self.participants
+            .iter()
+            .filter(|p| p.id != user_id)
+            .map(|p| p.name.clone())
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+/// A message of a conversation. Canvas keeps inbox messages as plain text
+pub struct ConversationMessage {
+    pub author: Option<String>,
+    pub body: String,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+/// Someone the user can message
+#[derive(Clone)]
+pub struct Recipient {
+    pub id: String,
+    pub name: String,
+    // Teacher or TA of the course the recipients were listed for
+    pub instructor: bool,
+}
+
+// Recipients whose name contains `query`, instructors first
+pub fn suggest<'a>(recipients: &'a [Recipient], query: &str) -> Vec<&'a Recipient> {
+    let query = query.to_lowercase();
+    let mut matches = recipients
+        .iter()
+        .filter(|r| r.name.to_lowercase().contains(&query))
+        .collect::<Vec<&Recipient>>();
+    matches.sort_by_key(|r| (!r.instructor, r.name.to_lowercase()));
+    matches
+}
@@ -0,0 +1,36 @@
+use chrono::{DateTime, Utc};
+
+pub struct Course {
+    pub id: u32,
+    pub name: String,
+    pub nickname: Option<String>,
+    pub term: Option<String>,
+    pub start_at: Option<DateTime<Utc>>,
+    pub end_at: Option<DateTime<Utc>>,
+}
+
+impl Course {
+    // A course is current if today falls inside its term (or its own dates when
+    // the term has none). Courses without any dates are treated as current
+    pub fn is_current(&self, now: DateTime<Utc>) -> bool {
+        let started = self.start_at.is_none_or(|start| start <= now);
+        let not_ended = self.end_at.is_none_or(|end| now <= end);
+        started && not_ended
+    }
+
+    pub fn display_name(&self) -> &str {
+        match &self.nickname {
+            Some(nickname) => nickname,
+            None => &self.name,
+        }
+    }
+}
+
+impl std::fmt::Display for Course {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.term {
+            Some(term) => write!(f, "{} ({}) [{}]", self.display_name(), term, self.id),
+            None => write!(f, "{} [{}]", self.display_name(), self.id),
+        }
+    }
+}
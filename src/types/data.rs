@@ -1,87 +1,479 @@
 use std::error::Error;
+use std::io::Write;
+use crate::types::announcement::Announcement;
 use crate::types::assignment::Assignment;
 use crate::types::grade::Grade;
+use crate::types::migrations;
 use serde::{Deserialize, Serialize};
-use std::cmp::Ordering;
+use chrono::{DateTime, Duration, Utc};
+use std::cmp::{Ordering, Reverse};
+use std::collections::HashMap;
+use std::path::Path;
+
+fn create_path(pathstr: &str) -> Result<(), Box<dyn Error>> {
+    let path = Path::new(pathstr);
+    if !path.exists() {
+        let parent = match path.parent() {
+            Some(p) => p,
+            None => return Err("Invalid path".into()),
+        };
+        std::fs::create_dir_all(parent)?;
+        std::fs::File::create(path)?;
+    }
+    Ok(())
+}
 
 #[derive(Serialize, Deserialize)]
 pub struct Data {
+    // Schema version, see types::migrations
+    #[serde(default)]
+    pub version: u64,
     pub assignments: Vec<Assignment>,
     pub grades: Vec<Grade>,
+    // When each course was last fetched without errors, keyed by course id
+    #[serde(default)]
+    pub last_synced: HashMap<u32, DateTime<Utc>>,
+    // Deleted custom assignments, so another instance doesn't bring them back on merge
+    #[serde(default)]
+    pub deleted: Vec<Tombstone>,
+    // Assignments past their due date, kept as a record of what was turned in
+    #[serde(default)]
+    pub archive: Vec<Assignment>,
+    // Recent announcements of every course, see `sort_announcements`
+    #[serde(default)]
+    pub announcements: Vec<Announcement>,
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Tombstone {
+    pub id: String,
+    pub at: DateTime<Utc>,
+}
+
+// Tombstones older than this are forgotten
+pub const TOMBSTONE_DAYS: i64 = 30;
+
 impl Data {
     pub fn empty() -> Self {
         Self {
+            version: migrations::VERSION,
             assignments: Vec::new(),
             grades: Vec::new(),
+            last_synced: HashMap::new(),
+            deleted: Vec::new(),
+            archive: Vec::new(),
+            announcements: Vec::new(),
         }
     }
 
-    pub fn remove_past_assignments(&mut self) {
-        let now = chrono::Utc::now();
-        self.assignments.retain(|a| {
-            if let Some(date) = a.date {
-                date > now
-            } else {
-                true
+    // Move everything past its due date into the archive, except for unfinished work that
+    // has been overdue for less than `overdue_for`
+    pub fn archive_past_assignments(&mut self, overdue_for: Duration) {
+        let cutoff = Utc::now() - overdue_for;
+        let (past, upcoming) = std::mem::take(&mut self.assignments)
+            .into_iter()
+            .partition::<Vec<Assignment>, _>(|a| {
+                a.is_past() && !(a.is_overdue() && a.date.is_some_and(|d| d > cutoff))
+            });
+        self.assignments = upcoming;
+        for a in past {
+            match self.archive.iter_mut().find(|o| o.id == a.id) {
+                Some(o) => *o = a,
+                None => self.archive.push(a),
             }
+        }
+    }
+
+    pub fn delete_assignment(&mut self, i: usize) {
+        let a = self.assignments.remove(i);
+        self.deleted.push(Tombstone {
+            id: a.id,
+            at: Utc::now(),
         });
     }
 
+    // Overdue work first, then upcoming assignments by due date, then the undated ones
     pub fn sort_assignments(&mut self) {
         self.assignments.sort_by(|a, b| {
-            if let (Some(a_date), Some(b_date)) = (a.date, b.date) {
-                match a_date.date_naive().cmp(&b_date.date_naive()) {
-                    Ordering::Equal => a.completed.cmp(&b.completed),
+            match b.is_overdue().cmp(&a.is_overdue()) {
+                Ordering::Equal => (),
+                o => return o,
+            }
+            match (a.date, b.date) {
+                (Some(a_date), Some(b_date)) => match a_date.date_naive().cmp(&b_date.date_naive()) {
+                    Ordering::Equal => a.is_finished().cmp(&b.is_finished()),
                     _ => a_date.cmp(&b_date),
-                }
-            } else if let Some(_) = a.date {
-                Ordering::Greater
-            } else {
-                Ordering::Less
+                },
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                // Undated ones need an order of their own for the sort to be total
+                (None, None) => a
+                    .is_finished()
+                    .cmp(&b.is_finished())
+                    .then_with(|| a.name.cmp(&b.name))
+                    .then_with(|| a.id.cmp(&b.id)),
             }
         });
     }
 
+    // Overdue assignments are always at the start of the list
+    pub fn number_overdue(&self) -> usize {
+        self.assignments.iter().take_while(|a| a.is_overdue()).count()
+    }
+
     pub fn update_assignments(&mut self, assignments: Vec<Assignment>) {
         for assignment in assignments {
-            if let Some(a) = self.assignments.iter_mut().find(|a| a.html_url == assignment.html_url) {
-                if !a.modified {
-                    a.completed |= assignment.completed;
-                }
-                a.locked = assignment.locked;
-                a.description = assignment.description;
-                a.date = assignment.date;
-                a.populate_links();
-            } else {
-                self.assignments.push(assignment);
+            let existing = self
+                .assignments
+                .iter_mut()
+                .chain(self.archive.iter_mut())
+                .find(|a| a.id == assignment.id);
+            match existing {
+                Some(a) => a.update_from(assignment),
+                None => self.assignments.push(assignment),
             }
         }
         self.sort_assignments();
     }
 
+    // Replace grades with freshly fetched ones, keeping the old grade of any course that failed to fetch.
+    // Each fetched grade is added to its course's history
+    pub fn update_grades(&mut self, grades: Vec<Grade>, failed_course_ids: &[u32]) {
+        let now = Utc::now();
+        let mut previous = std::mem::take(&mut self.grades);
+        self.grades = previous
+            .extract_if(.., |g| failed_course_ids.contains(&g.course_id))
+            .collect();
+        for mut g in grades {
+            let old = previous
+                .iter()
+                .position(|o| o.course_id == g.course_id)
+                .map(|i| previous.remove(i));
+            g.record(old, now);
+            self.grades.push(g);
+        }
+    }
+
+    // Replace announcements with freshly fetched ones, keeping what was read locally and the
+    // stored announcements of any course that failed to fetch
+    pub fn update_announcements(&mut self, fetched: Vec<Announcement>, failed_course_ids: &[u32]) {
+        let mut previous = std::mem::take(&mut self.announcements);
+        self.announcements = previous
+            .extract_if(.., |a| failed_course_ids.contains(&a.course_id))
+            .collect();
+        for f in fetched {
+            match previous.iter().position(|a| a.id == f.id) {
+                Some(i) => {
+                    let mut a = previous.remove(i);
+                    a.update_from(f);
+                    self.announcements.push(a);
+                }
+                None => self.announcements.push(f),
+            }
+        }
+        self.sort_announcements();
+    }
+
+    // Grouped by course, newest first within each
+    pub fn sort_announcements(&mut self) {
+        let mut announcements = std::mem::take(&mut self.announcements);
+        announcements.sort_by_cached_key(|a| (self.course_name(a.course_id), Reverse(a.posted_at)));
+        self.announcements = announcements;
+    }
+
+    pub fn number_unread(&self) -> usize {
+        self.announcements.iter().filter(|a| !a.read).count()
+    }
+
+    // Display name of a course, from whatever is stored for it
+    pub fn course_name(&self, course_id: u32) -> String {
+        self.grades
+            .iter()
+            .find(|g| g.course_id == course_id)
+            .map(|g| g.course.clone())
+            .or_else(|| {
+                self.assignments
+                    .iter()
+                    .chain(self.archive.iter())
+                    .find(|a| a.course_id == Some(course_id))
+                    .map(|a| a.course.clone())
+            })
+            .unwrap_or(format!("Course {}", course_id))
+    }
+
+    // Every course that has an assignment or a grade stored
+    pub fn course_ids(&self) -> Vec<u32> {
+        let mut ids = self
+            .assignments
+            .iter()
+            .filter_map(|a| a.course_id)
+            .chain(self.grades.iter().map(|g| g.course_id))
+            .collect::<Vec<u32>>();
+        ids.sort();
+        ids.dedup();
+        ids
+    }
+
+    pub fn mark_synced(&mut self, course_ids: &[u32]) {
+        let now = Utc::now();
+        for course_id in course_ids {
+            self.last_synced.insert(*course_id, now);
+        }
+    }
+
+    // A course is stale if it was never synced or not within `max_age`
+    pub fn is_stale(&self, course_id: u32, max_age: Duration) -> bool {
+        match self.last_synced.get(&course_id) {
+            Some(t) => Utc::now() - *t > max_age,
+            None => true,
+        }
+    }
+
     pub fn get_number_incomplete(&self) -> usize {
-        self.assignments.iter().filter(|a| !a.completed).count()
+        self.assignments.iter().filter(|a| !a.is_finished()).count()
     }
 
     pub fn serialize(&self) -> Result<String, Box<dyn Error>> {
         Ok(serde_json::to_string(self)?)
     }
 
-    pub fn serialize_to_file(&self, path: &str) -> Result<(), Box<dyn Error>> {
-        let data = self.serialize()?;
-        std::fs::write(path, data)?;
+    // Write to `path` safely with other instances around: take the data directory lock,
+    // merge in whatever is on disk, then write a temp file and rename it into place
+    pub fn serialize_to_file(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
+        let path = Path::new(path);
+        let dir = match path.parent() {
+            Some(p) => p,
+            None => return Err("Invalid path".into()),
+        };
+        std::fs::create_dir_all(dir)?;
+
+        // Advisory lock, released when the file is dropped
+        let lock = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(dir.join(".canvas-tui.lock"))?;
+        lock.lock()?;
+
+        if let Ok(contents) = std::fs::read_to_string(path) {
+            match Self::deserialize(&contents) {
+                Ok(theirs) => self.merge(theirs),
+                // Never clobber a file written by a newer build
+                Err(e) if Self::is_newer(&contents) => return Err(e),
+                Err(_) => (),
+            }
+        }
+
+        let data = Data::serialize(self)?;
+        let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("data.json");
+        let tmp = dir.join(format!(".{}.{}.tmp", file_name, std::process::id()));
+        {
+            let mut file = std::fs::File::create(&tmp)?;
+            file.write_all(data.as_bytes())?;
+            file.sync_all()?;
+        }
+        std::fs::rename(&tmp, path)?;
         Ok(())
     }
 
+    // Reconcile with a copy written by another instance: the newest user change to each
+    // assignment wins, newer syncs win for Canvas data and deletions are honoured
+    pub fn merge(&mut self, theirs: Data) {
+        let cutoff = Utc::now() - Duration::days(TOMBSTONE_DAYS);
+        for t in theirs.deleted {
+            if !self.deleted.iter().any(|d| d.id == t.id) {
+                self.deleted.push(t);
+            }
+        }
+        self.deleted.retain(|d| d.at > cutoff);
+
+        let deleted = self.deleted.clone();
+        let is_deleted = |a: &Assignment| {
+            deleted
+                .iter()
+                .any(|d| d.id == a.id && a.modified_at.is_none_or(|m| m <= d.at))
+        };
+        self.assignments.retain(|a| !is_deleted(a));
+        self.archive.retain(|a| !is_deleted(a));
+
+        let synced_later = |course_id: Option<u32>, ours: &HashMap<u32, DateTime<Utc>>| match course_id {
+            Some(id) => theirs.last_synced.get(&id) > ours.get(&id),
+            None => false,
+        };
+        let synced_earlier = |course_id: Option<u32>, ours: &HashMap<u32, DateTime<Utc>>| match course_id {
+            Some(id) => theirs.last_synced.get(&id) < ours.get(&id),
+            None => false,
+        };
+        let theirs_archive = theirs.archive.into_iter().map(|a| (a, true));
+        for (t, archived) in theirs.assignments.into_iter().map(|a| (a, false)).chain(theirs_archive) {
+            if is_deleted(&t) {
+                continue;
+            }
+            let newer_sync = synced_later(t.course_id, &self.last_synced);
+            let existing = self
+                .assignments
+                .iter_mut()
+                .chain(self.archive.iter_mut())
+                .find(|a| a.id == t.id);
+            match existing {
+                Some(a) => a.merge_from(t, newer_sync),
+                // Created by the other instance, or a Canvas assignment it fetched more recently
+                None if archived => self.archive.push(t),
+                None if t.custom || !synced_earlier(t.course_id, &self.last_synced) => {
+                    self.assignments.push(t)
+                }
+                None => (),
+            }
+        }
+
+        for g in theirs.grades {
+            let newer_sync = synced_later(Some(g.course_id), &self.last_synced);
+            match self.grades.iter_mut().find(|o| o.course_id == g.course_id) {
+                Some(o) if newer_sync => {
+                    let history = std::mem::take(&mut o.history);
+                    *o = g;
+                    o.merge_history(&history);
+                }
+                Some(o) => o.merge_history(&g.history),
+                None => self.grades.push(g),
+            }
+        }
+        // Read anywhere is read everywhere. One they have that we don't is only taken when they
+        // synced the course more recently, otherwise it aged out of our last fetch
+        for t in theirs.announcements {
+            match self.announcements.iter_mut().find(|a| a.id == t.id) {
+                Some(a) => a.read |= t.read,
+                None if !synced_earlier(Some(t.course_id), &self.last_synced) => self.announcements.push(t),
+                None => (),
+            }
+        }
+        self.sort_announcements();
+        for (id, t) in theirs.last_synced {
+            let ours = self.last_synced.entry(id).or_insert(t);
+            if t > *ours {
+                *ours = t;
+            }
+        }
+
+        self.sort_assignments();
+    }
+
     pub fn deserialize(data: &str) -> Result<Self, Box<dyn Error>> {
-        Ok(serde_json::from_str(data)?)
+        let mut value: serde_json::Value = serde_json::from_str(data)?;
+        migrations::migrate(&mut value)?;
+        Ok(serde_json::from_value(value)?)
+    }
+
+    fn is_newer(data: &str) -> bool {
+        serde_json::from_str(data).is_ok_and(|v| migrations::version_of(&v) > migrations::VERSION)
     }
 
-    pub fn deserialize_from_file(path: &str) -> Result<Self, Box<dyn Error>> {
-        let data = std::fs::read_to_string(path)?;
-        Self::deserialize(&data)
+    // Load the data file at path, creating it first if needed. If it can't be read it is
+    // backed up and replaced with empty data, and the returned message says where it went
+    pub fn load(path: &str) -> Result<(Self, Option<String>), Box<dyn Error>> {
+        create_path(path)?;
+        let contents = std::fs::read_to_string(path)?;
+        if contents.trim().is_empty() {
+            return Ok((Self::empty(), None));
+        }
+        match Self::deserialize(&contents) {
+            Ok(data) => Ok((data, None)),
+            Err(e) if Self::is_newer(&contents) => Err(format!("Could not load {}: {}", path, e).into()),
+            Err(e) => {
+                let backup = format!("{}.{}.bak", path, chrono::Local::now().format("%Y%m%d%H%M%S"));
+                std::fs::copy(path, &backup)?;
+                let message = format!("Could not load {} ({}), backed it up to {} and started fresh", path, e, backup);
+                Ok((Self::empty(), Some(message)))
+            }
+        }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn undated(name: &str, completed: bool) -> Assignment {
+        let mut a = Assignment::empty();
+        a.name = name.to_string();
+        a.date = None;
+        a.completed = completed;
+        a
+    }
+
+    fn announcement(id: &str) -> Announcement {
+        Announcement {
+            id: id.to_string(),
+            course_id: 1,
+            title: String::new(),
+            message: String::new(),
+            author: None,
+            posted_at: None,
+            html_url: String::new(),
+            read: false,
+            links: vec![],
+        }
+    }
+
+    #[test]
+    fn update_keeps_announcements_of_failed_courses() {
+        let mut data = Data::empty();
+        let mut other = announcement("2");
+        other.course_id = 2;
+        data.announcements = vec![announcement("1"), other];
+        data.update_announcements(vec![announcement("3")], &[2]);
+        let mut ids = data.announcements.iter().map(|a| a.id.as_str()).collect::<Vec<_>>();
+        ids.sort();
+        // Course 1 was fetched and "1" is gone from it, course 2 failed and keeps "2"
+        assert_eq!(ids, vec!["2", "3"]);
+    }
+
+    #[test]
+    fn merge_drops_announcements_that_aged_out() {
+        let now = Utc::now();
+        let mut ours = Data::empty();
+        ours.last_synced.insert(1, now);
+        ours.announcements = vec![announcement("2")];
+        let mut theirs = Data::empty();
+        theirs.last_synced.insert(1, now - Duration::hours(1));
+        theirs.announcements = vec![announcement("1"), announcement("2")];
+        ours.merge(theirs);
+        assert_eq!(ours.announcements.iter().map(|a| a.id.as_str()).collect::<Vec<_>>(), vec!["2"]);
+    }
+
+    #[test]
+    fn merge_adopts_announcements_synced_later() {
+        let now = Utc::now();
+        let mut ours = Data::empty();
+        ours.last_synced.insert(1, now - Duration::hours(1));
+        let mut theirs = Data::empty();
+        theirs.last_synced.insert(1, now);
+        theirs.announcements = vec![announcement("1")];
+        ours.merge(theirs);
+        assert_eq!(ours.announcements.len(), 1);
+    }
+
+    #[test]
+    fn undated_assignments_sort_last_and_totally() {
+        let mut data = Data::empty();
+        let mut dated = Assignment::empty();
+        dated.name = "Dated".to_string();
+        data.assignments = vec![
+            undated("C", false),
+            undated("A", true),
+            dated,
+            undated("B", false),
+            undated("A", false),
+        ];
+        data.sort_assignments();
+        let names = data
+            .assignments
+            .iter()
+            .map(|a| (a.name.as_str(), a.completed))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![("Dated", false), ("A", false), ("B", false), ("C", false), ("A", true)]
+        );
+    }
+}
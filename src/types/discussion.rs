@@ -0,0 +1,78 @@
+use chrono::{DateTime, Utc};
+use crate::types::html;
+
+/// A discussion topic of a course, announcements excluded
+pub struct Discussion {
+    // Canvas topic id
+    pub id: String,
+    pub course_id: u32,
+    pub title: String,
+    // HTML body of the topic itself
+    pub message: String,
+    pub author: Option<String>,
+    pub posted_at: Option<DateTime<Utc>>,
+    pub last_reply_at: Option<DateTime<Utc>>,
+    pub replies: i64,
+    pub unread: i64,
+    pub locked: bool,
+    // The assignment a graded discussion belongs to
+    pub assignment_id: Option<String>,
+}
+
+impl Discussion {
+    pub fn text(&self) -> String {
+        html::to_text(&self.message)
+    }
+
+    // Most recent activity, for sorting
+    pub fn active_at(&self) -> Option<DateTime<Utc>> {
+        self.last_reply_at.or(self.posted_at)
+    }
+
+    pub fn html_url(&self, base_url: &str) -> String {
+        format!("{}/courses/{}/discussion_topics/{}", base_url, self.course_id, self.id)
+    }
+}
+
+/// A reply to a topic or to another reply
+pub struct DiscussionEntry {
+    pub id: String,
+    // None for replies to the topic itself
+    pub parent_id: Option<String>,
+    pub author: Option<String>,
+    pub message: String,
+    pub created_at: Option<DateTime<Utc>>,
+    pub deleted: bool,
+    pub read: bool,
+}
+
+impl DiscussionEntry {
+    pub fn text(&self) -> String {
+        if self.deleted {
+            "(deleted)".to_string()
+        } else {
+            html::to_text(&self.message)
+        }
+    }
+}
+
+// Entries in reply tree order, each with its depth. Replies whose parent isn't among the
+// entries are shown at the top level rather than dropped
+pub fn thread(mut entries: Vec<DiscussionEntry>) -> Vec<(usize, DiscussionEntry)> {
+    entries.sort_by_key(|e| e.created_at);
+    let ids = entries.iter().map(|e| e.id.clone()).collect::<Vec<String>>();
+    let is_root = |e: &DiscussionEntry| e.parent_id.as_ref().is_none_or(|p| !ids.contains(p));
+    let (roots, mut replies): (Vec<DiscussionEntry>, Vec<DiscussionEntry>) = entries.into_iter().partition(is_root);
+
+    let mut threaded = vec![];
+    // Depth-first, children in the order they were posted
+    let mut stack = roots.into_iter().rev().map(|e| (0, e)).collect::<Vec<(usize, DiscussionEntry)>>();
+    while let Some((depth, entry)) = stack.pop() {
+        let children = replies
+            .extract_if(.., |r| r.parent_id.as_ref() == Some(&entry.id))
+            .collect::<Vec<DiscussionEntry>>();
+        stack.extend(children.into_iter().rev().map(|c| (depth + 1, c)));
+        threaded.push((depth, entry));
+    }
+    threaded
+}
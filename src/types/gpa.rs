@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+use crate::types::grade::Grade;
+
+// Grade points on the usual 4.0 scale
+const GRADE_POINTS: [(&str, f64); 13] = [
+    ("A+", 4.0),
+    ("A", 4.0),
+    ("A-", 3.7),
+    ("B+", 3.3),
+    ("B", 3.0),
+    ("B-", 2.7),
+    ("C+", 2.3),
+    ("C", 2.0),
+    ("C-", 1.7),
+    ("D+", 1.3),
+    ("D", 1.0),
+    ("D-", 0.7),
+    ("F", 0.0),
+];
+
+pub fn grade_points(letter: &str) -> Option<f64> {
+    GRADE_POINTS
+        .iter()
+        .find(|(l, _)| l.eq_ignore_ascii_case(letter.trim()))
+        .map(|(_, points)| *points)
+}
+
+/// One course's part in the term GPA
+pub struct CourseGpa {
+    pub course: String,
+    pub percent: f64,
+    pub letter: Option<String>,
+    pub credits: Option<f64>,
+    pub points: Option<f64>,
+}
+
+impl CourseGpa {
+    // Only courses with credit hours and a letter on the 4.0 scale count
+    pub fn counts(&self) -> bool {
+        self.credits.is_some() && self.points.is_some()
+    }
+}
+
+/// Term and cumulative GPA projected from the current letter grades
+pub struct GpaSummary {
+    pub courses: Vec<CourseGpa>,
+    pub term_credits: f64,
+    pub term_gpa: Option<f64>,
+    pub prior_gpa: Option<f64>,
+    pub prior_credits: f64,
+    pub cumulative_gpa: Option<f64>,
+}
+
+impl GpaSummary {
+    pub fn new(grades: &[Grade], credits: &HashMap<u32, f64>, prior_gpa: Option<f64>, prior_credits: f64) -> Self {
+        let courses = grades
+            .iter()
+            .map(|g| {
+                let letter = g.letter();
+                CourseGpa {
+                    course: g.course.clone(),
+                    percent: g.grade,
+                    points: letter.as_deref().and_then(grade_points),
+                    letter,
+                    credits: credits.get(&g.course_id).copied(),
+                }
+            })
+            .collect::<Vec<CourseGpa>>();
+
+        let (term_credits, term_points) = courses
+            .iter()
+            .filter_map(|c| Some((c.credits?, c.points?)))
+            .fold((0.0, 0.0), |(credits, points), (c, p)| (credits + c, points + c * p));
+        let term_gpa = if term_credits > 0.0 { Some(term_points / term_credits) } else { None };
+
+        // Prior terms only count if their GPA was given
+        let prior_credits = if prior_gpa.is_some() { prior_credits } else { 0.0 };
+        let total_credits = term_credits + prior_credits;
+        let cumulative_gpa = if total_credits > 0.0 {
+            Some((term_points + prior_gpa.unwrap_or(0.0) * prior_credits) / total_credits)
+        } else {
+            None
+        };
+
+        Self {
+            courses,
+            term_credits,
+            term_gpa,
+            prior_gpa,
+            prior_credits,
+            cumulative_gpa,
+        }
+    }
+}
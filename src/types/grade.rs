@@ -1,10 +1,141 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Grade {
     pub course: String,
     pub grade: f64,
-    course_id: u32,
+    pub course_id: u32,
+    // Whether the course weights its assignment groups
+    #[serde(default)]
+    pub weighted: bool,
+    #[serde(default)]
+    pub groups: Vec<AssignmentGroup>,
+    // Letter grades as Canvas reports them for the enrollment
+    #[serde(default)]
+    pub current_grade: Option<String>,
+    #[serde(default)]
+    pub final_grade: Option<String>,
+    // The course's grading scheme, highest cutoff first
+    #[serde(default)]
+    pub scheme: Vec<Cutoff>,
+    // Grade over time, oldest first
+    #[serde(default)]
+    pub history: Vec<GradeSnapshot>,
+    // The grade as the user last saw it, to point out changes
+    #[serde(default)]
+    pub seen: Option<f64>,
+}
+
+/// A course grade at the time of a refresh
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GradeSnapshot {
+    pub at: DateTime<Utc>,
+    pub grade: f64,
+}
+
+/// One step of a grading scheme, e.g. "A-" from 90%
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Cutoff {
+    pub name: String,
+    // Lowest percentage that still gets this grade
+    pub min: f64,
+}
+
+// The what-if solver looks for scores up to this many times the points possible, past
+// the points possible being extra credit
+pub const SOLVE_MAX_FACTOR: f64 = 10.0;
+const SOLVE_STEPS: usize = 1000;
+
+// Canvas's scheme for courses that enable letter grades without picking one
+pub const DEFAULT_SCHEME: [(&str, f64); 12] = [
+    ("A", 94.0),
+    ("A-", 90.0),
+    ("B+", 87.0),
+    ("B", 84.0),
+    ("B-", 80.0),
+    ("C+", 77.0),
+    ("C", 74.0),
+    ("C-", 70.0),
+    ("D+", 67.0),
+    ("D", 64.0),
+    ("D-", 61.0),
+    ("F", 0.0),
+];
+
+/// A Canvas assignment group with its weight, drop rules and graded work
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AssignmentGroup {
+    pub id: u32,
+    pub name: String,
+    // Percentage of the final grade, only used if the course is weighted
+    pub weight: f64,
+    pub drop_lowest: usize,
+    pub drop_highest: usize,
+    // Assignment ids the drop rules never apply to
+    pub never_drop: Vec<String>,
+    pub assignments: Vec<GroupAssignment>,
+}
+
+/// An assignment that counts towards its group's grade
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GroupAssignment {
+    pub id: String,
+    pub name: String,
+    pub score: Option<f64>,
+    pub points_possible: f64,
+}
+
+/// How one group adds up to the course total
+pub struct GroupBreakdown {
+    pub name: String,
+    pub score: f64,
+    pub possible: f64,
+    pub weight: Option<f64>,
+    // Percentage points this group adds to the total
+    pub contribution: Option<f64>,
+    pub graded: usize,
+    pub dropped: usize,
+}
+
+impl GroupBreakdown {
+    pub fn percent(&self) -> Option<f64> {
+        if self.possible > 0.0 {
+            Some(self.score / self.possible * 100.0)
+        } else {
+            None
+        }
+    }
+}
+
+impl AssignmentGroup {
+    // Graded work left after applying the drop rules, and how many were dropped.
+    // Lowest and highest are ranked by percentage like Canvas does
+    pub fn kept(&self) -> (Vec<&GroupAssignment>, usize) {
+        let mut graded = self
+            .assignments
+            .iter()
+            .filter(|a| a.score.is_some())
+            .collect::<Vec<&GroupAssignment>>();
+        let percent = |a: &GroupAssignment| match a.points_possible {
+            p if p > 0.0 => a.score.unwrap_or(0.0) / p,
+            _ => f64::INFINITY,
+        };
+        graded.sort_by(|a, b| percent(a).total_cmp(&percent(b)));
+
+        let (mut fixed, mut droppable): (Vec<&GroupAssignment>, Vec<&GroupAssignment>) =
+            graded.into_iter().partition(|a| self.never_drop.contains(&a.id));
+        let before = droppable.len();
+        // Always keep at least one assignment
+        let lowest = self.drop_lowest.min(droppable.len().saturating_sub(1));
+        droppable.drain(..lowest);
+        let highest = self.drop_highest.min(droppable.len().saturating_sub(1));
+        droppable.truncate(droppable.len() - highest);
+        let dropped = before - droppable.len();
+        fixed.extend(droppable);
+        (fixed, dropped)
+    }
 }
 
 impl Grade {
@@ -19,8 +150,188 @@ impl Grade {
             course,
             grade,
             course_id,
+            weighted: false,
+            groups: vec![],
+            current_grade: None,
+            final_grade: None,
+            scheme: vec![],
+            history: vec![],
+            seen: None,
+        }
+    }
+
+    // Take over the history and seen grade of the previously stored copy of this course, and
+    // record the current grade. An unchanged grade is recorded at most once a day
+    pub fn record(&mut self, previous: Option<Grade>, at: DateTime<Utc>) {
+        match previous {
+            Some(previous) => {
+                self.history = previous.history;
+                self.seen = previous.seen;
+            }
+            // Nothing to compare a new course against
+            None => self.seen = Some(self.grade),
+        }
+        let unchanged = self
+            .history
+            .last()
+            .is_some_and(|s| s.grade == self.grade && s.at.date_naive() == at.date_naive());
+        if !unchanged {
+            self.history.push(GradeSnapshot { at, grade: self.grade });
         }
     }
+
+    // Snapshots of both copies, for merging another instance's data
+    pub fn merge_history(&mut self, theirs: &[GradeSnapshot]) {
+        for snapshot in theirs {
+            if !self.history.iter().any(|s| s.at == snapshot.at) {
+                self.history.push(snapshot.clone());
+            }
+        }
+        self.history.sort_by_key(|s| s.at);
+    }
+
+    // How far the grade moved since the user last saw it
+    pub fn change(&self) -> Option<f64> {
+        self.seen
+            .map(|seen| self.grade - seen)
+            .filter(|change| change.abs() > f64::EPSILON)
+    }
+
+    pub fn mark_seen(&mut self) {
+        self.seen = Some(self.grade);
+    }
+
+    // Step of the scheme a percentage falls in
+    pub fn cutoff_for(&self, percent: f64) -> Option<&Cutoff> {
+        self.scheme.iter().find(|c| percent >= c.min)
+    }
+
+    // Canvas's letter grade if it gave one, otherwise looked up in the scheme
+    pub fn letter(&self) -> Option<String> {
+        self.current_grade
+            .clone()
+            .or_else(|| self.cutoff_for(self.grade).map(|c| c.name.clone()))
+    }
+
+    // The next grade up and how many percentage points it's away
+    pub fn next_cutoff_up(&self) -> Option<(&Cutoff, f64)> {
+        self.scheme
+            .iter()
+            .rev()
+            .find(|c| c.min > self.grade)
+            .map(|c| (c, c.min - self.grade))
+    }
+
+    // The next grade down and how many percentage points can be lost before getting it
+    pub fn next_cutoff_down(&self) -> Option<(&Cutoff, f64)> {
+        let i = self.scheme.iter().position(|c| self.grade >= c.min)?;
+        let floor = self.scheme[i].min;
+        self.scheme.get(i + 1).map(|c| (c, self.grade - floor))
+    }
+
+    // Score, weight and share of the total for each group. Groups without graded work
+    // don't count, so the weights of the others are scaled up like Canvas does
+    pub fn breakdown(&self) -> Vec<GroupBreakdown> {
+        let mut groups = self
+            .groups
+            .iter()
+            .map(|g| {
+                let (kept, dropped) = g.kept();
+                GroupBreakdown {
+                    name: g.name.clone(),
+                    score: kept.iter().filter_map(|a| a.score).sum(),
+                    possible: kept.iter().map(|a| a.points_possible).sum(),
+                    weight: if self.weighted { Some(g.weight) } else { None },
+                    contribution: None,
+                    graded: kept.len(),
+                    dropped,
+                }
+            })
+            .collect::<Vec<GroupBreakdown>>();
+
+        if self.weighted {
+            let total_weight: f64 = groups
+                .iter()
+                .filter(|g| g.percent().is_some())
+                .filter_map(|g| g.weight)
+                .sum();
+            for g in groups.iter_mut() {
+                if let (Some(percent), Some(weight)) = (g.percent(), g.weight)
+                    && total_weight > 0.0
+                {
+                    g.contribution = Some(percent * weight / total_weight);
+                }
+            }
+        } else {
+            let total_possible: f64 = groups.iter().map(|g| g.possible).sum();
+            for g in groups.iter_mut() {
+                if total_possible > 0.0 && g.possible > 0.0 {
+                    g.contribution = Some(g.score / total_possible * 100.0);
+                }
+            }
+        }
+        groups
+    }
+
+    // Course percentage as computed from the groups, None without any graded work
+    pub fn computed_total(&self) -> Option<f64> {
+        let contributions = self
+            .breakdown()
+            .into_iter()
+            .filter_map(|g| g.contribution)
+            .collect::<Vec<f64>>();
+        if contributions.is_empty() {
+            None
+        } else {
+            Some(contributions.iter().sum())
+        }
+    }
+
+    // Copy of this grade with hypothetical scores (by assignment id) filled in
+    pub fn with_scores(&self, scores: &HashMap<String, f64>) -> Grade {
+        let mut grade = self.clone();
+        for a in grade.groups.iter_mut().flat_map(|g| g.assignments.iter_mut()) {
+            if let Some(score) = scores.get(&a.id) {
+                a.score = Some(*score);
+            }
+        }
+        grade
+    }
+
+    // Lowest score on `assignment_id` that gets the total to `target`, given the other
+    // hypothetical scores. None if even SOLVE_MAX_FACTOR times the points possible isn't enough
+    pub fn solve(&self, scores: &HashMap<String, f64>, assignment_id: &str, target: f64) -> Option<f64> {
+        // One copy with the other scores filled in, only the solved-for score changes between tries
+        let mut grade = self.with_scores(scores);
+        let (g, a) = grade.groups.iter().enumerate().find_map(|(g, group)| {
+            group.assignments.iter().position(|a| a.id == assignment_id).map(|a| (g, a))
+        })?;
+        let possible = grade.groups[g].assignments[a].points_possible.max(1.0);
+        let mut total_with = |score: f64| {
+            grade.groups[g].assignments[a].score = Some(score);
+            grade.computed_total().unwrap_or(0.0)
+        };
+
+        if total_with(0.0) >= target {
+            return Some(0.0);
+        }
+        // Drop rules rank by percentage, so more points can change what gets dropped and lower
+        // the total, e.g. once this score becomes the one dropped by drop_highest. Scan for the
+        // first step that's enough and only bisect within it
+        let step = possible * SOLVE_MAX_FACTOR / SOLVE_STEPS as f64;
+        let first = (1..=SOLVE_STEPS).find(|i| total_with(*i as f64 * step) >= target)?;
+        let (mut low, mut high) = ((first - 1) as f64 * step, first as f64 * step);
+        for _ in 0..50 {
+            let mid = (low + high) / 2.0;
+            if total_with(mid) >= target {
+                high = mid;
+            } else {
+                low = mid;
+            }
+        }
+        // Round up to the hundredth so the answer is actually enough, minus float noise
+        Some(((high * 100.0) - 1e-6).ceil() / 100.0)
+    }
 }
 
 impl std::fmt::Display for Grade {
@@ -28,3 +339,53 @@ impl std::fmt::Display for Grade {
         write!(f, "{}: {}", self.course, self.grade)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assignment(id: &str, score: Option<f64>, points_possible: f64) -> GroupAssignment {
+        GroupAssignment {
+            id: id.to_string(),
+            name: id.to_string(),
+            score,
+            points_possible,
+        }
+    }
+
+    fn grade(drop_highest: usize, assignments: Vec<GroupAssignment>) -> Grade {
+        let mut grade = Grade::new("Biology".to_string(), None, 0.0, 1);
+        grade.groups = vec![AssignmentGroup {
+            id: 1,
+            name: "Labs".to_string(),
+            weight: 100.0,
+            drop_lowest: 0,
+            drop_highest,
+            never_drop: vec![],
+            assignments,
+        }];
+        grade
+    }
+
+    #[test]
+    fn solves_for_lowest_score() {
+        let grade = grade(0, vec![assignment("a", Some(50.0), 100.0), assignment("b", None, 100.0)]);
+        assert_eq!(grade.solve(&HashMap::new(), "b", 75.0), Some(100.0));
+        assert_eq!(grade.solve(&HashMap::new(), "b", 60.0), Some(70.0));
+        assert_eq!(grade.solve(&HashMap::new(), "b", 25.0), Some(0.0));
+        // Past the points possible it's extra credit, far past it out of reach
+        assert_eq!(grade.solve(&HashMap::new(), "b", 100.0), Some(150.0));
+        assert_eq!(grade.solve(&HashMap::new(), "b", 600.0), None);
+    }
+
+    #[test]
+    fn more_points_can_lower_the_total() {
+        // While "c" is below 100% the perfect "b" is dropped as the highest, above it "c" is
+        let grade = grade(
+            1,
+            vec![assignment("a", Some(5.0), 10.0), assignment("b", Some(10.0), 10.0), assignment("c", None, 100.0)],
+        );
+        assert!(grade.with_scores(&HashMap::from([("c".to_string(), 1000.0)])).computed_total().unwrap() < 80.0);
+        assert_eq!(grade.solve(&HashMap::new(), "c", 80.0), Some(83.0));
+    }
+}
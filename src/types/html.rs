@@ -0,0 +1,138 @@
+use select::document::Document;
+use select::node::Node;
+use select::predicate::Name;
+use crate::types::link::Link;
+
+// Every <a href> in a piece of Canvas HTML, titled with its text
+pub fn links(html: &str) -> Vec<Link> {
+    Document::from(html)
+        .find(Name("a"))
+        .filter_map(|n| {
+            let url = n.attr("href")?;
+            Some(Link::new(url.to_string(), n.text()))
+        })
+        .collect()
+}
+
+// Canvas HTML as plain text for the terminal: paragraphs and line breaks kept, lists
+// bulleted, everything else flattened
+pub fn to_text(html: &str) -> String {
+    let document = Document::from(html);
+    let mut text = String::new();
+    match document.find(Name("body")).next() {
+        Some(body) => walk(body, &mut text),
+        None => text = document.find(Name("html")).map(|n| n.text()).collect(),
+    }
+
+    // At most one blank line in a row, and no trailing spaces
+    let mut out = String::new();
+    let mut blank = 0;
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() {
+            blank += 1;
+            continue;
+        }
+        if !out.is_empty() {
+            out.push_str(if blank > 0 { "\n\n" } else { "\n" });
+        }
+        out.push_str(line);
+        blank = 0;
+    }
+    out
+}
+
+// Plain text written by the user as HTML for Canvas, one paragraph per blank-line separated block
+pub fn from_text(text: &str) -> String {
+    let escaped = text
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    escaped
+        .split("\n\n")
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(|p| format!("<p>{}</p>", p.lines().collect::<Vec<&str>>().join("<br>")))
+        .collect()
+}
+
+fn walk(node: Node, out: &mut String) {
+    if let Some(text) = node.as_text() {
+        let collapsed = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+        if text.starts_with(char::is_whitespace) && !out.ends_with([' ', '\n']) && !out.is_empty() {
+            out.push(' ');
+        }
+        out.push_str(&collapsed);
+        if text.ends_with(char::is_whitespace) && !collapsed.is_empty() {
+            out.push(' ');
+        }
+        return;
+    }
+    let children = |out: &mut String| {
+        for child in node.children() {
+            walk(child, out);
+        }
+    };
+    match node.name() {
+        Some("script" | "style" | "head") => (),
+        Some("br") => out.push('\n'),
+        Some("li") => {
+            if !out.ends_with('\n') {
+                out.push('\n');
+            }
+            out.push_str("• ");
+            children(out);
+            out.push('\n');
+        }
+        Some("p" | "div" | "ul" | "ol" | "table" | "blockquote" | "pre" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6") => {
+            out.push_str("\n\n");
+            children(out);
+            out.push_str("\n\n");
+        }
+        Some("tr") => {
+            out.push('\n');
+            children(out);
+            out.push('\n');
+        }
+        _ => children(out),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_keep_href_and_text() {
+        let links = links(r#"<p>See <a href="https://x/a?b=1&amp;c=2">the <b>notes</b></a> and <a>no href</a></p>"#);
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].url, "https://x/a?b=1&c=2");
+        assert_eq!(links[0].title, "the notes");
+    }
+
+    #[test]
+    fn to_text_keeps_paragraphs_and_bullets() {
+        let html = "<p>First   line<br>second</p>\n<ul><li>one</li><li>two</li></ul><p>Last</p>";
+        assert_eq!(to_text(html), "First line\nsecond\n\n• one\n• two\n\nLast");
+    }
+
+    #[test]
+    fn to_text_drops_scripts_and_decodes_entities() {
+        let html = "<div>a &lt;b&gt; &amp; c</div><script>alert(1)</script><style>p {}</style>";
+        assert_eq!(to_text(html), "a <b> & c");
+    }
+
+    #[test]
+    fn from_text_escapes_and_splits_paragraphs() {
+        assert_eq!(
+            from_text("a < b & c\nnext line\n\n<script>x</script>\n\n\n"),
+            "<p>a &lt; b &amp; c<br>next line</p><p>&lt;script&gt;x&lt;/script&gt;</p>"
+        );
+        assert_eq!(from_text("  \n\n "), "");
+    }
+
+    #[test]
+    fn from_text_round_trips_through_to_text() {
+        let text = "Tom & Jerry <3\nsecond line\n\nNew paragraph";
+        assert_eq!(to_text(&from_text(text)), text);
+    }
+}
@@ -0,0 +1,241 @@
+use serde_json::{Map, Value};
+use std::error::Error;
+
+// Current on-disk schema version of the data file
+pub const VERSION: u64 = 9;
+
+type Migration = fn(&mut Map<String, Value>) -> Result<(), Box<dyn Error>>;
+
+// MIGRATIONS[n] upgrades a version n file to version n + 1
+const MIGRATIONS: [Migration; VERSION as usize] = [
+    v0_to_v1, v1_to_v2, v2_to_v3, v3_to_v4, v4_to_v5, v5_to_v6, v6_to_v7, v7_to_v8, v8_to_v9,
+];
+
+// Files written before the version field existed are version 0
+pub fn version_of(value: &Value) -> u64 {
+    value.get("version").and_then(Value::as_u64).unwrap_or(0)
+}
+
+// Upgrade a parsed data file to the current version, one step at a time
+pub fn migrate(value: &mut Value) -> Result<(), Box<dyn Error>> {
+    let from = version_of(value);
+    if from > VERSION {
+        return Err(format!(
+            "data file is version {}, but this build only understands up to version {}",
+            from, VERSION
+        )
+        .into());
+    }
+    let data = match value.as_object_mut() {
+        Some(data) => data,
+        None => return Err("data file is not a JSON object".into()),
+    };
+    for migration in &MIGRATIONS[from as usize..] {
+        migration(data)?;
+    }
+    data.insert("version".to_string(), Value::from(VERSION));
+    Ok(())
+}
+
+fn assignments_mut(data: &mut Map<String, Value>) -> impl Iterator<Item = &mut Map<String, Value>> {
+    data.get_mut("assignments")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_object_mut)
+}
+
+// v1: course ids, sync times, modification times and tombstones
+fn v0_to_v1(data: &mut Map<String, Value>) -> Result<(), Box<dyn Error>> {
+    for a in assignments_mut(data) {
+        a.entry("course_id").or_insert(Value::Null);
+        a.entry("modified_at").or_insert(Value::Null);
+        a.entry("locked").or_insert(Value::Bool(false));
+        a.entry("links").or_insert(Value::Array(Vec::new()));
+    }
+    data.entry("grades").or_insert(Value::Array(Vec::new()));
+    data.entry("last_synced").or_insert(Value::Object(Map::new()));
+    data.entry("deleted").or_insert(Value::Array(Vec::new()));
+    Ok(())
+}
+
+// v2: stable assignment ids. Canvas ids come from the assignment URL and custom assignments
+// get a fresh UUID, identical ones included. Tombstones of Canvas assignments get the same
+// URL to id mapping, while ones keyed by what v1 matched custom assignments by can't apply
+// to the new ids, so they're dropped
+fn v1_to_v2(data: &mut Map<String, Value>) -> Result<(), Box<dyn Error>> {
+    for a in assignments_mut(data) {
+        let html_url = a.get("html_url").and_then(Value::as_str).unwrap_or_default();
+        let id = if a.get("custom").and_then(Value::as_bool).unwrap_or(false) {
+            uuid::Uuid::new_v4().to_string()
+        } else {
+            canvas_id(html_url)
+        };
+        a.insert("id".to_string(), Value::from(id));
+    }
+    if let Some(deleted) = data.get_mut("deleted").and_then(Value::as_array_mut) {
+        deleted.retain(|t| {
+            let key = t.get("key").and_then(Value::as_str).unwrap_or_default();
+            !key.starts_with("custom:")
+        });
+        for t in deleted.iter_mut().filter_map(Value::as_object_mut) {
+            if let Some(key) = t.remove("key") {
+                let id = canvas_id(key.as_str().unwrap_or_default());
+                t.insert("id".to_string(), Value::from(id));
+            }
+        }
+    }
+    Ok(())
+}
+
+// The numeric id at the end of a Canvas assignment URL, or the URL itself without one
+fn canvas_id(html_url: &str) -> String {
+    match html_url.rsplit_once("/assignments/") {
+        Some((_, id)) if !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()) => id.to_string(),
+        _ => html_url.to_string(),
+    }
+}
+
+// v3: archive of past assignments, with term, submission status and score
+fn v2_to_v3(data: &mut Map<String, Value>) -> Result<(), Box<dyn Error>> {
+    for a in assignments_mut(data) {
+        a.entry("term").or_insert(Value::Null);
+        a.entry("submission_status").or_insert(Value::Null);
+        a.entry("score").or_insert(Value::Null);
+    }
+    data.entry("archive").or_insert(Value::Array(Vec::new()));
+    Ok(())
+}
+
+// v4: submission_status strings become a submission state, plus submit time and attempt.
+// `completed` used to be set whenever Canvas had a submission, only the user's own toggles
+// (`modified`) are kept as the manual done flag
+fn v3_to_v4(data: &mut Map<String, Value>) -> Result<(), Box<dyn Error>> {
+    for key in ["assignments", "archive"] {
+        let list = match data.get_mut(key).and_then(Value::as_array_mut) {
+            Some(list) => list,
+            None => continue,
+        };
+        for a in list.iter_mut().filter_map(Value::as_object_mut) {
+            let state = match a.remove("submission_status").as_ref().and_then(Value::as_str) {
+                Some(s @ ("submitted" | "graded" | "late" | "missing")) => s.to_string(),
+                Some("resubmitted") => "submitted".to_string(),
+                _ => "unsubmitted".to_string(),
+            };
+            a.insert("submission".to_string(), Value::from(state));
+            a.entry("submitted_at").or_insert(Value::Null);
+            a.entry("attempt").or_insert(Value::Null);
+            if !a.get("modified").and_then(Value::as_bool).unwrap_or(false) {
+                a.insert("completed".to_string(), Value::Bool(false));
+            }
+        }
+    }
+    Ok(())
+}
+
+// v5: points possible, grading type and grade
+fn v4_to_v5(data: &mut Map<String, Value>) -> Result<(), Box<dyn Error>> {
+    for key in ["assignments", "archive"] {
+        let list = match data.get_mut(key).and_then(Value::as_array_mut) {
+            Some(list) => list,
+            None => continue,
+        };
+        for a in list.iter_mut().filter_map(Value::as_object_mut) {
+            a.entry("grade").or_insert(Value::Null);
+            a.entry("points_possible").or_insert(Value::Null);
+            a.entry("grading_type").or_insert(Value::Null);
+        }
+    }
+    Ok(())
+}
+
+// v6: assignment groups and weighting for each course grade
+fn v5_to_v6(data: &mut Map<String, Value>) -> Result<(), Box<dyn Error>> {
+    let grades = data.get_mut("grades").and_then(Value::as_array_mut).into_iter().flatten();
+    for g in grades.filter_map(Value::as_object_mut) {
+        g.entry("weighted").or_insert(Value::Bool(false));
+        g.entry("groups").or_insert(Value::Array(Vec::new()));
+    }
+    Ok(())
+}
+
+// v7: letter grades and the course grading scheme
+fn v6_to_v7(data: &mut Map<String, Value>) -> Result<(), Box<dyn Error>> {
+    let grades = data.get_mut("grades").and_then(Value::as_array_mut).into_iter().flatten();
+    for g in grades.filter_map(Value::as_object_mut) {
+        g.entry("current_grade").or_insert(Value::Null);
+        g.entry("final_grade").or_insert(Value::Null);
+        g.entry("scheme").or_insert(Value::Array(Vec::new()));
+    }
+    Ok(())
+}
+
+// v8: grade history, and what the user has seen of new grades
+fn v7_to_v8(data: &mut Map<String, Value>) -> Result<(), Box<dyn Error>> {
+    let grades = data.get_mut("grades").and_then(Value::as_array_mut).into_iter().flatten();
+    for g in grades.filter_map(Value::as_object_mut) {
+        g.entry("history").or_insert(Value::Array(Vec::new()));
+        // Whatever was stored has been on screen already
+        let grade = g.get("grade").cloned().unwrap_or(Value::Null);
+        g.entry("seen").or_insert(grade);
+    }
+    for key in ["assignments", "archive"] {
+        let list = match data.get_mut(key).and_then(Value::as_array_mut) {
+            Some(list) => list,
+            None => continue,
+        };
+        for a in list.iter_mut().filter_map(Value::as_object_mut) {
+            a.entry("new_grade").or_insert(Value::Bool(false));
+        }
+    }
+    Ok(())
+}
+
+// v9: course announcements
+fn v8_to_v9(data: &mut Map<String, Value>) -> Result<(), Box<dyn Error>> {
+    data.entry("announcements").or_insert(Value::Array(Vec::new()));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn v2_gives_custom_assignments_unique_ids() {
+        let custom = json!({ "custom": true, "course": "Bio", "name": "Lab", "date": "2024-01-01T00:00:00Z", "html_url": "" });
+        let mut data = json!({
+            "assignments": [
+                custom.clone(),
+                custom,
+                { "custom": false, "html_url": "https://x/courses/1/assignments/42" },
+            ],
+            "deleted": [
+                { "key": "custom:Bio:Lab:2024-01-01T00:00:00Z", "at": "2024-01-02T00:00:00Z" },
+                { "key": "https://x/courses/1/assignments/7", "at": "2024-01-02T00:00:00Z" },
+            ],
+        });
+        v1_to_v2(data.as_object_mut().unwrap()).unwrap();
+        let a = &data["assignments"];
+        assert_ne!(a[0]["id"], a[1]["id"]);
+        assert!(uuid::Uuid::parse_str(a[0]["id"].as_str().unwrap()).is_ok());
+        assert_eq!(a[2]["id"], json!("42"));
+        // The tombstone matches the dismissed assignment's new id
+        assert_eq!(data["deleted"], json!([{ "id": "7", "at": "2024-01-02T00:00:00Z" }]));
+    }
+
+    #[test]
+    fn v4_drops_automatic_completion() {
+        let mut data = json!({
+            "assignments": [
+                { "completed": true, "modified": false, "submission_status": "submitted" },
+                { "completed": true, "modified": true, "submission_status": "submitted" },
+            ],
+        });
+        v3_to_v4(data.as_object_mut().unwrap()).unwrap();
+        assert_eq!(data["assignments"][0]["completed"], json!(false));
+        assert_eq!(data["assignments"][0]["submission"], json!("submitted"));
+        assert_eq!(data["assignments"][1]["completed"], json!(true));
+    }
+}
@@ -1,4 +1,12 @@
+pub mod announcement;
 pub mod assignment;
+pub mod conversation;
+pub mod course;
+pub mod discussion;
 pub mod grade;
+pub mod html;
+pub mod gpa;
 pub mod data;
 pub mod link;
+pub mod migrations;
+pub mod submission;
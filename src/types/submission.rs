@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+
+/// Where the user's submission for an assignment stands according to Canvas
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SubmissionState {
+    #[default]
+    Unsubmitted,
+    Submitted,
+    Graded,
+    Late,
+    Missing,
+    Excused,
+    ResubmissionNeeded,
+}
+
+impl SubmissionState {
+    // Canvas reports these as separate flags, the most actionable one wins
+    pub fn from_canvas(state: &str, late: bool, missing: bool, excused: bool, redo_request: bool) -> Self {
+        if excused {
+            Self::Excused
+        } else if redo_request {
+            Self::ResubmissionNeeded
+        } else if missing {
+            Self::Missing
+        } else if state == "graded" {
+            Self::Graded
+        } else if late {
+            Self::Late
+        } else if state == "submitted" || state == "pending_review" {
+            Self::Submitted
+        } else {
+            Self::Unsubmitted
+        }
+    }
+
+    // Nothing left to do for the user
+    pub fn is_turned_in(&self) -> bool {
+        matches!(self, Self::Submitted | Self::Graded | Self::Late | Self::Excused)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Unsubmitted => "unsubmitted",
+            Self::Submitted => "submitted",
+            Self::Graded => "graded",
+            Self::Late => "late",
+            Self::Missing => "missing",
+            Self::Excused => "excused",
+            Self::ResubmissionNeeded => "resubmission_needed",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        [
+            Self::Unsubmitted,
+            Self::Submitted,
+            Self::Graded,
+            Self::Late,
+            Self::Missing,
+            Self::Excused,
+            Self::ResubmissionNeeded,
+        ]
+        .into_iter()
+        .find(|state| state.as_str() == s)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Unsubmitted => "Not submitted",
+            Self::Submitted => "Submitted",
+            Self::Graded => "Graded",
+            Self::Late => "Late",
+            Self::Missing => "Missing",
+            Self::Excused => "Excused",
+            Self::ResubmissionNeeded => "Resubmit",
+        }
+    }
+}
@@ -1,10 +1,19 @@
 use ratatui::widgets::{ListState, TableState};
 use tokio::sync::Mutex;
-use std::hash::{DefaultHasher, Hasher};
-use std::{hash::Hash, time::Duration};
+use chrono::{DateTime, FixedOffset};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::time::Duration;
 use std::sync::Arc;
 use std::error::Error;
-use crate::types::{assignment::Assignment, data::Data};
+use crate::config::{Profile, Settings};
+use crate::queries::client::CanvasClient;
+use crate::storage::{self, Storage};
+use crate::types::conversation::{self, Conversation, ConversationMessage, Recipient};
+use crate::types::discussion::{self, Discussion, DiscussionEntry};
+use crate::types::html;
+use crate::types::{announcement::Announcement, assignment::Assignment, data::Data, gpa::GpaSummary, grade::{Grade, GroupAssignment}};
+use super::status::Status;
 
 #[derive(PartialEq, Eq, Copy, Clone)]
 pub enum AssignmentField {
@@ -18,67 +27,240 @@ pub enum AssignmentField {
 pub enum Mode {
     Normal,
     NewAssignment(AssignmentField),
+    Log,
+    Archive,
+    // Grades pane focused, and the breakdown of the selected course open on top of it
+    Grades,
+    Breakdown,
+    // Hypothetical scores on top of the breakdown, typing either a score or a target percentage
+    WhatIf(WhatIfField),
+    // Term summary with the projected GPA
+    Term,
+    // Tab with the course announcements
+    Announcements,
+    // Tab with the discussion topics, and one topic's replies opened from it
+    Discussions,
+    Thread,
+    // Tab with the Canvas inbox, one conversation opened from it, and a new message's recipients
+    Inbox,
+    Conversation,
+    NewMessage(MessageField),
+}
+
+/// Something written in $EDITOR, sent once the editor exits
+pub enum Compose {
+    // Reply to a discussion topic, or to one of its entries
+    Reply { topic_id: String, parent_id: Option<String> },
+    // Message to everyone else in an inbox conversation
+    Message { conversation_id: String, recipients: Vec<String> },
+    // New inbox conversation in a course, the subject is taken from the first line
+    NewMessage { course_id: u32, recipients: Vec<String> },
+}
+
+impl Compose {
+    // What the editor starts out with
+    pub fn template(&self) -> &'static str {
+        match self {
+            Compose::NewMessage { .. } => "Subject: \n\n",
+            Compose::Reply { .. } | Compose::Message { .. } => "",
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Copy, Clone)]
+pub enum MessageField {
+    Course,
+    Recipients,
+}
+
+/// A new inbox message while its recipients are being picked
+pub struct NewMessage {
+    // Index into `Data::course_ids`
+    pub course_i: usize,
+    pub recipients: Vec<Recipient>,
+    // Search typed in the recipients field
+    pub input: String,
+    pub suggestion: usize,
+}
+
+#[derive(PartialEq, Eq, Copy, Clone)]
+pub enum WhatIfField {
+    Score,
+    Target,
 }
 
 pub struct App {
-    pub data_path: String,
+    pub storage: Box<dyn Storage>,
     pub tick_rate: Duration,
-    course_ids: Vec<u32>,
+    pub profiles: Vec<Profile>,
+    pub profile_i: usize,
+    pub client: CanvasClient,
+    pub settings: Settings,
     pub assignments_state: TableState,
     pub links_state: ListState,
     pub data: Data,
     pub mode: Mode,
+    pub status: Status,
+    pub log_state: ListState,
+    pub archive_groups_state: ListState,
+    pub archive_state: TableState,
+    // Show the "score / possible" column in the assignments table
+    pub show_scores: bool,
+    pub grades_state: TableState,
+    // What-if scores as typed, by assignment id. Only kept for the session
+    pub what_if: HashMap<String, String>,
+    pub what_if_state: TableState,
+    pub what_if_target: String,
+    // Result of `solve_what_if`, redone when the scores, target, selection or grades change
+    pub what_if_solution: Option<(GroupAssignment, f64, Option<f64>)>,
+    pub announcements_state: TableState,
+    pub announcement_links_state: ListState,
+    // Lines scrolled down in the announcement body
+    pub announcement_scroll: u16,
+    // Discussions are fetched when their tab is first opened and not stored
+    pub discussions: Option<Vec<Discussion>>,
+    pub discussions_state: TableState,
+    // Assignment whose discussion should be selected once the topics are in
+    pub pending_discussion: Option<String>,
+    pub thread_topic: Option<String>,
+    // Replies of the open topic in tree order with their depth, None while loading
+    pub thread: Option<Vec<(usize, DiscussionEntry)>>,
+    pub thread_state: ListState,
+    // Picked up by the main loop, which has the terminal to hand over to the editor
+    pub compose: Option<Compose>,
+    // Canvas id of the user, looked up the first time the inbox needs it
+    pub user_id: Option<String>,
+    // Like discussions, conversations are fetched on demand and not stored
+    pub conversations: Option<Vec<Conversation>>,
+    pub conversations_state: TableState,
+    pub open_conversation: Option<String>,
+    // Messages of the open conversation, None while loading
+    pub messages: Option<Vec<ConversationMessage>>,
+    pub message_scroll: u16,
+    // Messageable users by course, fetched when a new message is started there
+    pub recipients: HashMap<u32, Vec<Recipient>>,
+    pub new_message: Option<NewMessage>,
 }
 
 impl App {
-    pub fn new(data_path: String, tick_rate: Duration, course_ids: Vec<u32>, data: Data) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        storage: Box<dyn Storage>,
+        tick_rate: Duration,
+        profiles: Vec<Profile>,
+        profile_i: usize,
+        client: CanvasClient,
+        settings: Settings,
+        data: Data,
+    ) -> Self {
         App {
-            data_path,
+            storage,
             tick_rate,
-            course_ids,
+            profiles,
+            profile_i,
+            client,
+            settings,
             assignments_state: TableState::default(),
             links_state: ListState::default(),
             data,
             mode: Mode::Normal,
+            status: Status::new(),
+            log_state: ListState::default(),
+            archive_groups_state: ListState::default(),
+            archive_state: TableState::default(),
+            show_scores: false,
+            grades_state: TableState::default(),
+            what_if: HashMap::new(),
+            what_if_state: TableState::default(),
+            what_if_target: String::new(),
+            what_if_solution: None,
+            announcements_state: TableState::default(),
+            announcement_links_state: ListState::default(),
+            announcement_scroll: 0,
+            discussions: None,
+            discussions_state: TableState::default(),
+            pending_discussion: None,
+            thread_topic: None,
+            thread: None,
+            thread_state: ListState::default(),
+            compose: None,
+            user_id: None,
+            conversations: None,
+            conversations_state: TableState::default(),
+            open_conversation: None,
+            messages: None,
+            message_scroll: 0,
+            recipients: HashMap::new(),
+            new_message: None,
         }
     }
 
-    pub fn select_by_hash(&mut self, hash: Option<u64>) {
-        if let Some(h) = hash {
+    pub fn profile(&self) -> &Profile {
+        &self.profiles[self.profile_i]
+    }
+
+    // Save the current profile's data and rebuild the app around the next profile
+    pub fn next_profile(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.profiles.len() < 2 {
+            return Ok(());
+        }
+        self.serialize_data()?;
+        let profile_i = (self.profile_i + 1) % self.profiles.len();
+        let profile = &self.profiles[profile_i];
+        let token = match profile.token() {
+            Ok(token) => token,
+            Err(_) if self.settings.offline => String::new(),
+            Err(e) => return Err(e),
+        };
+        let client = self.client.with_instance(profile.base_url.clone(), token);
+        let mut storage = storage::open(profile, self.settings.storage)?;
+        let (data, load_error) = storage.load()?;
+        let status = std::mem::replace(&mut self.status, Status::new());
+        *self = App::new(
+            storage,
+            self.tick_rate,
+            std::mem::take(&mut self.profiles),
+            profile_i,
+            client,
+            self.settings.clone(),
+            data,
+        );
+
+        // The log outlives the profile, the refresh state doesn't
+        self.status.log = status.log;
+        self.status.info(format!("Switched to profile {}", self.profile().name));
+        if let Some(e) = load_error {
+            self.status.error(e);
+        }
+        Ok(())
+    }
+
+    pub fn select_by_id(&mut self, id: Option<String>) {
+        if let Some(id) = id {
             let new_i = self
                 .data
                 .assignments
                 .iter()
-                .enumerate()
-                .find(|a| {
-                    let mut hasher = DefaultHasher::new();
-                    a.1.hash(&mut hasher);
-                    hasher.finish() == h
-                })
-                .map_or_else(|| 0, |v| v.0);
+                .position(|a| a.id == id)
+                .unwrap_or(0);
             self.assignments_state.select(Some(new_i));
         }
     }
 
-    pub fn get_selected_hash(&self) -> Option<u64> {
-        let mut selected_hash = None;
-        if let Some(selected_i) = self.assignments_state.selected() {
-            let mut hasher = DefaultHasher::new();
-            self.data.assignments[selected_i].hash(&mut hasher);
-            selected_hash = Some(hasher.finish());
-        }
-        selected_hash
+    pub fn get_selected_id(&self) -> Option<String> {
+        self.assignments_state
+            .selected()
+            .and_then(|i| self.data.assignments.get(i))
+            .map(|a| a.id.clone())
     }
 
     pub async fn new_assignment(&mut self) -> Result<(), Box<dyn Error>> {
         self.mode = Mode::NewAssignment(AssignmentField::Course);
-        match self.mode {
-            Mode::Normal => return Err("Cannot create new assignment in normal mode.".into()),
-            Mode::NewAssignment(_) => {
-                self.data.assignments.insert(0, Assignment::empty());
-                self.assignments_state.select(Some(0));
-            }
+        if !matches!(self.mode, Mode::NewAssignment(_)) {
+            return Err("Cannot create new assignment in normal mode.".into());
         }
+        self.data.assignments.insert(0, Assignment::empty());
+        self.assignments_state.select(Some(0));
 
         Ok(())
     }
@@ -97,8 +279,8 @@ impl App {
                 return Ok(());
             }
 
-            self.data.assignments.remove(i);
-            if self.data.assignments.len() == 0 {
+            self.data.delete_assignment(i);
+            if self.data.assignments.is_empty() {
                 self.assignments_state.select(None);
             } else if self.data.assignments.len() <= i {
                 self.assignments_state.select(Some(i - 1));
@@ -119,7 +301,7 @@ impl App {
 
     pub async fn open_link(&self) {
         if let (Some(link_i), Some(assignment_i)) = (self.links_state.selected(), self.assignments_state.selected()) {
-            if self.data.assignments[assignment_i].links.len() == 0 {
+            if self.data.assignments[assignment_i].links.is_empty() {
                 return;
             }
             let url = self.data.assignments[assignment_i].links[link_i].url.clone();
@@ -134,19 +316,16 @@ impl App {
             let assignment = &mut self.data.assignments[a];
             assignment.completed = !assignment.completed;
             assignment.modified = true;
+            assignment.touch();
         }
-        let selected_hash = self.get_selected_hash();
+        let selected_id = self.get_selected_id();
         self.data.sort_assignments();
-        self.select_by_hash(selected_hash);
+        self.select_by_id(selected_id);
     }
 
-    pub fn enter(&mut self) {
-        ()
-    }
+    pub fn enter(&mut self) {}
 
-    pub fn esc(&mut self) {
-        ()
-    }
+    pub fn esc(&mut self) {}
 
     pub fn next_assignment(&mut self) {
         if let Some(selected) = self.assignments_state.selected() {
@@ -156,16 +335,17 @@ impl App {
                 selected + 1
             };
             self.assignments_state.select(Some(next));
-        } else if self.data.assignments.len() > 0 {
+        } else if !self.data.assignments.is_empty() {
             self.assignments_state.select(Some(0));
         }
 
         // Select first link if an assignment is selected
-        if let Some(i) = self.assignments_state.selected() {
-            if self.data.assignments[i].links.len() > 0 {
-                self.links_state.select(Some(0));
-            }
+        if let Some(i) = self.assignments_state.selected()
+            && !self.data.assignments[i].links.is_empty()
+        {
+            self.links_state.select(Some(0));
         }
+        self.see_selected_assignment();
     }
 
     pub fn prev_assignment(&mut self) {
@@ -176,9 +356,34 @@ impl App {
                 selected - 1
             };
             self.assignments_state.select(Some(prev));
-        } else if self.data.assignments.len() > 0 {
+        } else if !self.data.assignments.is_empty() {
             self.assignments_state.select(Some(0));
         }
+        self.see_selected_assignment();
+    }
+
+    // Moving onto an assignment counts as having seen its new grade
+    fn see_selected_assignment(&mut self) {
+        if let Some(a) = self.assignments_state.selected().and_then(|i| self.data.assignments.get_mut(i)) {
+            a.new_grade = false;
+        }
+    }
+
+    // Grades usually post once an assignment is archived, so it's seen there too
+    fn see_selected_archived(&mut self) {
+        let id = self
+            .archive_state
+            .selected()
+            .and_then(|i| self.archive_selection().get(i).map(|a| a.id.clone()));
+        if let Some(a) = id.and_then(|id| self.data.archive.iter_mut().find(|a| a.id == id)) {
+            a.new_grade = false;
+        }
+    }
+
+    fn see_selected_grade(&mut self) {
+        if let Some(g) = self.grades_state.selected().and_then(|i| self.data.grades.get_mut(i)) {
+            g.mark_seen();
+        }
     }
 
     pub fn next_link(&mut self) {
@@ -203,55 +408,1183 @@ impl App {
         }
     }
 
-    pub fn serialize_data(&self) -> Result<(), Box<dyn Error>> {
-        self.data.serialize_to_file(&self.data_path)
+    // Save, picking up changes made by other instances in the meantime
+    pub fn serialize_data(&mut self) -> Result<(), Box<dyn Error>> {
+        let selected_id = self.get_selected_id();
+        self.storage.save(&mut self.data)?;
+        self.select_by_id(selected_id);
+        Ok(())
+    }
+
+    // Cached data for this course may be out of date
+    pub fn is_stale(&self, course_id: Option<u32>) -> bool {
+        match course_id {
+            Some(id) => self.data.is_stale(id, self.settings.stale_after),
+            None => false,
+        }
+    }
+
+    pub fn toggle_grades(&mut self) {
+        self.mode = match self.mode {
+            Mode::Grades | Mode::Breakdown | Mode::WhatIf(_) => Mode::Normal,
+            _ => {
+                if self.grades_state.selected().is_none_or(|i| i >= self.data.grades.len()) {
+                    let first = if self.data.grades.is_empty() { None } else { Some(0) };
+                    self.grades_state.select(first);
+                }
+                self.see_selected_grade();
+                Mode::Grades
+            }
+        };
+    }
+
+    pub fn next_grade(&mut self) {
+        if let Some(i) = self.grades_state.selected()
+            && i + 1 < self.data.grades.len()
+        {
+            self.grades_state.select(Some(i + 1));
+        }
+        self.see_selected_grade();
+    }
+
+    pub fn prev_grade(&mut self) {
+        if let Some(i) = self.grades_state.selected() {
+            self.grades_state.select(Some(i.saturating_sub(1)));
+        }
+        self.see_selected_grade();
+    }
+
+    pub fn selected_grade(&self) -> Option<&Grade> {
+        self.grades_state.selected().and_then(|i| self.data.grades.get(i))
+    }
+
+    pub fn toggle_breakdown(&mut self) {
+        self.mode = match self.mode {
+            Mode::Grades if self.selected_grade().is_some() => Mode::Breakdown,
+            Mode::Breakdown => Mode::Grades,
+            mode => mode,
+        };
+    }
+
+    // Ungraded assignments of the selected course with their group name, the ones a what-if score can go on
+    pub fn what_if_assignments(&self) -> Vec<(String, GroupAssignment)> {
+        self.selected_grade()
+            .map(|grade| {
+                grade
+                    .groups
+                    .iter()
+                    .flat_map(|g| g.assignments.iter().map(move |a| (g.name.clone(), a.clone())))
+                    .filter(|(_, a)| a.score.is_none())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    // The typed what-if scores that parse
+    pub fn what_if_scores(&self) -> HashMap<String, f64> {
+        self.what_if
+            .iter()
+            .filter_map(|(id, score)| score.parse::<f64>().ok().map(|score| (id.clone(), score)))
+            .collect()
+    }
+
+    pub fn toggle_what_if(&mut self) {
+        self.mode = match self.mode {
+            Mode::Breakdown => {
+                let first = if self.what_if_assignments().is_empty() { None } else { Some(0) };
+                self.what_if_state.select(first);
+                self.solve_what_if();
+                Mode::WhatIf(WhatIfField::Score)
+            }
+            Mode::WhatIf(_) => Mode::Breakdown,
+            mode => mode,
+        };
+    }
+
+    pub fn toggle_what_if_target(&mut self) {
+        self.mode = match self.mode {
+            Mode::WhatIf(WhatIfField::Score) => Mode::WhatIf(WhatIfField::Target),
+            Mode::WhatIf(WhatIfField::Target) => Mode::WhatIf(WhatIfField::Score),
+            mode => mode,
+        };
+    }
+
+    pub fn next_what_if(&mut self) {
+        if let Some(i) = self.what_if_state.selected()
+            && i + 1 < self.what_if_assignments().len()
+        {
+            self.what_if_state.select(Some(i + 1));
+            self.solve_what_if();
+        }
+    }
+
+    pub fn prev_what_if(&mut self) {
+        if let Some(i) = self.what_if_state.selected() {
+            self.what_if_state.select(Some(i.saturating_sub(1)));
+            self.solve_what_if();
+        }
+    }
+
+    pub fn selected_what_if(&self) -> Option<GroupAssignment> {
+        self.what_if_state
+            .selected()
+            .and_then(|i| self.what_if_assignments().into_iter().nth(i))
+            .map(|(_, a)| a)
+    }
+
+    // Type into the selected assignment's score or the target, whichever is being edited
+    pub fn what_if_push(&mut self, c: char) {
+        match self.mode {
+            Mode::WhatIf(WhatIfField::Score) => {
+                if let Some(a) = self.selected_what_if() {
+                    self.what_if.entry(a.id).or_default().push(c);
+                }
+            }
+            Mode::WhatIf(WhatIfField::Target) => self.what_if_target.push(c),
+            _ => (),
+        }
+        self.solve_what_if();
+    }
+
+    pub fn what_if_pop(&mut self) {
+        match self.mode {
+            Mode::WhatIf(WhatIfField::Score) => {
+                if let Some(a) = self.selected_what_if()
+                    && let Some(score) = self.what_if.get_mut(&a.id)
+                {
+                    score.pop();
+                    if score.is_empty() {
+                        self.what_if.remove(&a.id);
+                    }
+                }
+            }
+            Mode::WhatIf(WhatIfField::Target) => {
+                self.what_if_target.pop();
+            }
+            _ => (),
+        }
+        self.solve_what_if();
+    }
+
+    // Minimum score on the selected assignment to reach the target, with the other what-if
+    // scores in place. Outer None when there's nothing to solve, inner None when out of reach
+    fn what_if_needed(&self) -> Option<(GroupAssignment, f64, Option<f64>)> {
+        let target = self.what_if_target.parse::<f64>().ok()?;
+        let grade = self.selected_grade()?;
+        let assignment = self.selected_what_if()?;
+        let mut scores = self.what_if_scores();
+        scores.remove(&assignment.id);
+        let needed = grade.solve(&scores, &assignment.id, target);
+        Some((assignment, target, needed))
+    }
+
+    // Solving takes over a thousand evaluations of the grade, so it's done on edits rather than every redraw
+    pub fn solve_what_if(&mut self) {
+        self.what_if_solution = self.what_if_needed();
+    }
+
+    pub fn toggle_scores(&mut self) {
+        self.show_scores = !self.show_scores;
+    }
+
+    pub fn toggle_log(&mut self) {
+        self.mode = match self.mode {
+            Mode::Log => Mode::Normal,
+            _ => {
+                // Start at the newest entry
+                let last = self.status.log.len().checked_sub(1);
+                self.log_state.select(last);
+                Mode::Log
+            }
+        };
+    }
+
+    pub fn next_log_entry(&mut self) {
+        if let Some(i) = self.log_state.selected()
+            && i + 1 < self.status.log.len()
+        {
+            self.log_state.select(Some(i + 1));
+        }
+    }
+
+    pub fn prev_log_entry(&mut self) {
+        if let Some(i) = self.log_state.selected() {
+            self.log_state.select(Some(i.saturating_sub(1)));
+        }
+    }
+
+    // (term, course) pairs in the archive, most recently due first
+    pub fn archive_groups(&self) -> Vec<(String, String)> {
+        let mut latest: HashMap<(String, String), Option<DateTime<FixedOffset>>> = HashMap::new();
+        for a in &self.data.archive {
+            let key = (a.term.clone().unwrap_or("No term".to_string()), a.course.clone());
+            let date = latest.entry(key).or_default();
+            *date = (*date).max(a.date);
+        }
+        let mut groups = latest.into_iter().collect::<Vec<_>>();
+        groups.sort_by_key(|g| (Reverse(g.1), g.0.clone()));
+        groups.into_iter().map(|g| g.0).collect()
+    }
+
+    // Archived assignments of the selected course and term, most recently due first
+    pub fn archive_selection(&self) -> Vec<&Assignment> {
+        let groups = self.archive_groups();
+        let (term, course) = match self.archive_groups_state.selected().and_then(|i| groups.get(i)) {
+            Some(g) => g,
+            None => return vec![],
+        };
+        let mut assignments = self
+            .data
+            .archive
+            .iter()
+            .filter(|a| a.term.as_deref().unwrap_or("No term") == term && &a.course == course)
+            .collect::<Vec<&Assignment>>();
+        assignments.sort_by_key(|a| Reverse(a.date));
+        assignments
+    }
+
+    pub fn toggle_term(&mut self) {
+        self.mode = match self.mode {
+            Mode::Term => Mode::Normal,
+            _ => Mode::Term,
+        };
+    }
+
+    // Recomputed from the current grades, so it follows every refresh
+    pub fn gpa_summary(&self) -> GpaSummary {
+        let profile = self.profile();
+        GpaSummary::new(&self.data.grades, &profile.credits, profile.prior_gpa, profile.prior_credits)
+    }
+
+    // Tab from the assignments to the announcements to the discussions and back
+    pub fn next_tab(&mut self) {
+        self.mode = match self.mode {
+            Mode::Normal => {
+                if self.announcements_state.selected().is_none_or(|i| i >= self.data.announcements.len()) {
+                    let first = if self.data.announcements.is_empty() { None } else { Some(0) };
+                    self.select_announcement(first);
+                }
+                Mode::Announcements
+            }
+            Mode::Announcements => Mode::Discussions,
+            Mode::Discussions => Mode::Inbox,
+            _ => Mode::Normal,
+        };
+    }
+
+    pub fn selected_discussion(&self) -> Option<&Discussion> {
+        self.discussions_state
+            .selected()
+            .zip(self.discussions.as_ref())
+            .and_then(|(i, d)| d.get(i))
+    }
+
+    pub fn next_discussion(&mut self) {
+        let n = self.discussions.as_ref().map_or(0, |d| d.len());
+        if let Some(i) = self.discussions_state.selected()
+            && i + 1 < n
+        {
+            self.discussions_state.select(Some(i + 1));
+        }
+    }
+
+    pub fn prev_discussion(&mut self) {
+        if let Some(i) = self.discussions_state.selected() {
+            self.discussions_state.select(Some(i.saturating_sub(1)));
+        }
+    }
+
+    pub fn open_discussion(&self) {
+        if let Some(d) = self.selected_discussion() {
+            let url = d.html_url(&self.profile().base_url);
+            tokio::task::spawn(async move {
+                let _ = open::that(url);
+            });
+        }
+    }
+
+    // Jump from a graded discussion's assignment to its topic
+    pub fn show_discussion(&mut self) {
+        if let Some(id) = self.get_selected_id() {
+            self.pending_discussion = Some(id);
+            self.mode = Mode::Discussions;
+            self.select_pending_discussion();
+        }
+    }
+
+    fn select_pending_discussion(&mut self) {
+        let discussions = match &self.discussions {
+            Some(d) => d,
+            None => return,
+        };
+        if let Some(id) = self.pending_discussion.take() {
+            match discussions.iter().position(|d| d.assignment_id.as_ref() == Some(&id)) {
+                Some(i) => self.discussions_state.select(Some(i)),
+                None => self.status.info("No discussion found for this assignment".to_string()),
+            }
+        }
+    }
+
+    // The stored assignment a graded discussion belongs to
+    pub fn discussion_assignment(&self, d: &Discussion) -> Option<&Assignment> {
+        let id = d.assignment_id.as_ref()?;
+        self.data
+            .assignments
+            .iter()
+            .chain(self.data.archive.iter())
+            .find(|a| &a.id == id)
+    }
+
+    pub fn close_thread(&mut self) {
+        self.mode = Mode::Discussions;
+        self.thread_topic = None;
+        self.thread = None;
+    }
+
+    pub fn next_entry(&mut self) {
+        let n = self.thread.as_ref().map_or(0, |t| t.len());
+        if let Some(i) = self.thread_state.selected()
+            && i + 1 < n
+        {
+            self.thread_state.select(Some(i + 1));
+        }
+    }
+
+    pub fn prev_entry(&mut self) {
+        if let Some(i) = self.thread_state.selected() {
+            self.thread_state.select(Some(i.saturating_sub(1)));
+        }
+    }
+
+    pub fn selected_conversation(&self) -> Option<&Conversation> {
+        self.conversations_state
+            .selected()
+            .zip(self.conversations.as_ref())
+            .and_then(|(i, c)| c.get(i))
+    }
+
+    pub fn next_conversation(&mut self) {
+        let n = self.conversations.as_ref().map_or(0, |c| c.len());
+        if let Some(i) = self.conversations_state.selected()
+            && i + 1 < n
+        {
+            self.conversations_state.select(Some(i + 1));
+        }
+    }
+
+    pub fn prev_conversation(&mut self) {
+        if let Some(i) = self.conversations_state.selected() {
+            self.conversations_state.select(Some(i.saturating_sub(1)));
+        }
+    }
+
+    pub fn number_unread_conversations(&self) -> usize {
+        self.conversations.as_ref().map_or(0, |c| c.iter().filter(|c| c.unread).count())
+    }
+
+    pub fn close_conversation(&mut self) {
+        self.mode = Mode::Inbox;
+        self.open_conversation = None;
+        self.messages = None;
+    }
+
+    pub fn scroll_messages(&mut self, down: bool) {
+        self.message_scroll = if down {
+            self.message_scroll.saturating_add(5)
+        } else {
+            self.message_scroll.saturating_sub(5)
+        };
+    }
+
+    // Write to everyone else in the open conversation
+    pub fn reply_to_conversation(&mut self) {
+        let conversation = match self.open_conversation.as_ref().and_then(|id| {
+            self.conversations.as_ref()?.iter().find(|c| &c.id == id)
+        }) {
+            Some(c) => c,
+            None => return,
+        };
+        if !conversation.can_reply {
+            self.status.error("This conversation can't be replied to".to_string());
+            return;
+        }
+        let user_id = self.user_id.clone().unwrap_or_default();
+        let recipients = conversation
+            .participants
+            .iter()
+            .filter(|p| p.id != user_id)
+            .map(|p| p.id.clone())
+            .collect();
+        self.compose = Some(Compose::Message {
+            conversation_id: conversation.id.clone(),
+            recipients,
+        });
+    }
+
+    // Start a new message in the course of the selected assignment, or the first one
+    pub fn start_new_message(&mut self) {
+        let course_ids = self.data.course_ids();
+        if course_ids.is_empty() {
+            self.status.error("No courses to send a message in".to_string());
+            return;
+        }
+        let course_i = self
+            .assignments_state
+            .selected()
+            .and_then(|i| self.data.assignments.get(i))
+            .and_then(|a| a.course_id)
+            .and_then(|id| course_ids.iter().position(|c| *c == id))
+            .unwrap_or(0);
+        self.new_message = Some(NewMessage {
+            course_i,
+            recipients: vec![],
+            input: String::new(),
+            suggestion: 0,
+        });
+        self.mode = Mode::NewMessage(MessageField::Recipients);
+    }
+
+    pub fn cancel_new_message(&mut self) {
+        self.new_message = None;
+        self.mode = Mode::Inbox;
+    }
+
+    pub fn new_message_course(&self) -> Option<u32> {
+        let message = self.new_message.as_ref()?;
+        self.data.course_ids().get(message.course_i).copied()
+    }
+
+    pub fn toggle_message_field(&mut self) {
+        self.mode = match self.mode {
+            Mode::NewMessage(MessageField::Course) => Mode::NewMessage(MessageField::Recipients),
+            _ => Mode::NewMessage(MessageField::Course),
+        };
+    }
+
+    // Recipients are per course, so switching courses drops the ones picked so far
+    pub fn cycle_message_course(&mut self, forward: bool) {
+        let n = self.data.course_ids().len();
+        if let Some(message) = self.new_message.as_mut()
+            && n > 0
+        {
+            message.course_i = if forward {
+                (message.course_i + 1) % n
+            } else {
+                (message.course_i + n - 1) % n
+            };
+            message.recipients.clear();
+            message.suggestion = 0;
+        }
+    }
+
+    // Messageable users of the course matching the search, minus the ones already picked
+    pub fn recipient_suggestions(&self) -> Vec<&Recipient> {
+        let (message, course_id) = match self.new_message.as_ref().zip(self.new_message_course()) {
+            Some(m) => m,
+            None => return vec![],
+        };
+        let recipients = match self.recipients.get(&course_id) {
+            Some(r) => r,
+            None => return vec![],
+        };
+        conversation::suggest(recipients, &message.input)
+            .into_iter()
+            .filter(|r| Some(&r.id) != self.user_id.as_ref() && !message.recipients.iter().any(|p| p.id == r.id))
+            .collect()
+    }
+
+    pub fn recipient_push(&mut self, c: char) {
+        if let Some(message) = self.new_message.as_mut() {
+            message.input.push(c);
+            message.suggestion = 0;
+        }
+    }
+
+    // Delete a character of the search, or the last recipient once it's empty
+    pub fn recipient_pop(&mut self) {
+        if let Some(message) = self.new_message.as_mut() {
+            if message.input.pop().is_none() {
+                message.recipients.pop();
+            }
+            message.suggestion = 0;
+        }
+    }
+
+    pub fn next_suggestion(&mut self) {
+        let n = self.recipient_suggestions().len();
+        if let Some(message) = self.new_message.as_mut()
+            && message.suggestion + 1 < n
+        {
+            message.suggestion += 1;
+        }
+    }
+
+    pub fn prev_suggestion(&mut self) {
+        if let Some(message) = self.new_message.as_mut() {
+            message.suggestion = message.suggestion.saturating_sub(1);
+        }
+    }
+
+    // Add the highlighted suggestion, or once the search is empty and someone was picked,
+    // hand the message over to the editor
+    pub fn recipient_enter(&mut self) {
+        let suggestion = self
+            .new_message
+            .as_ref()
+            .and_then(|m| self.recipient_suggestions().get(m.suggestion).map(|r| (*r).clone()));
+        let course_id = self.new_message_course();
+        let message = match self.new_message.as_mut() {
+            Some(m) => m,
+            None => return,
+        };
+        if message.input.is_empty() && !message.recipients.is_empty() {
+            if let Some(course_id) = course_id {
+                self.compose = Some(Compose::NewMessage {
+                    course_id,
+                    recipients: message.recipients.iter().map(|r| r.id.clone()).collect(),
+                });
+            }
+            self.new_message = None;
+            self.mode = Mode::Inbox;
+        } else if let Some(r) = suggestion {
+            message.recipients.push(r);
+            message.input.clear();
+            message.suggestion = 0;
+        }
+    }
+
+    // Reply to the selected entry, or to the topic itself
+    pub fn reply(&mut self, to_entry: bool) {
+        let topic = match self.thread_topic.as_ref().and_then(|id| {
+            self.discussions.as_ref()?.iter().find(|d| &d.id == id)
+        }) {
+            Some(topic) => topic,
+            None => return,
+        };
+        if topic.locked {
+            self.status.error("This discussion is locked".to_string());
+            return;
+        }
+        let parent_id = if to_entry {
+            match self.thread_state.selected().zip(self.thread.as_ref()).and_then(|(i, t)| t.get(i)) {
+                Some((_, entry)) => Some(entry.id.clone()),
+                None => return,
+            }
+        } else {
+            None
+        };
+        self.compose = Some(Compose::Reply {
+            topic_id: topic.id.clone(),
+            parent_id,
+        });
+    }
+
+    pub fn selected_announcement(&self) -> Option<&Announcement> {
+        self.announcements_state
+            .selected()
+            .and_then(|i| self.data.announcements.get(i))
+    }
+
+    fn select_announcement(&mut self, i: Option<usize>) {
+        self.announcements_state.select(i);
+        self.announcement_scroll = 0;
+        let first_link = match self.selected_announcement() {
+            Some(a) if !a.links.is_empty() => Some(0),
+            _ => None,
+        };
+        self.announcement_links_state.select(first_link);
+    }
+
+    pub fn next_announcement(&mut self) {
+        if let Some(i) = self.announcements_state.selected()
+            && i + 1 < self.data.announcements.len()
+        {
+            self.select_announcement(Some(i + 1));
+        }
+    }
+
+    pub fn prev_announcement(&mut self) {
+        if let Some(i) = self.announcements_state.selected() {
+            self.select_announcement(Some(i.saturating_sub(1)));
+        }
+    }
+
+    pub fn scroll_announcement(&mut self, down: bool) {
+        self.announcement_scroll = if down {
+            self.announcement_scroll.saturating_add(5)
+        } else {
+            self.announcement_scroll.saturating_sub(5)
+        };
+    }
+
+    pub fn next_announcement_link(&mut self) {
+        let n = self.selected_announcement().map_or(0, |a| a.links.len());
+        if let Some(i) = self.announcement_links_state.selected()
+            && i + 1 < n
+        {
+            self.announcement_links_state.select(Some(i + 1));
+        }
+    }
+
+    pub fn prev_announcement_link(&mut self) {
+        if let Some(i) = self.announcement_links_state.selected() {
+            self.announcement_links_state.select(Some(i.saturating_sub(1)));
+        }
+    }
+
+    pub fn open_announcement(&self) {
+        if let Some(a) = self.selected_announcement() {
+            let url = a.html_url.clone();
+            tokio::task::spawn(async move {
+                let _ = open::that(url);
+            });
+        }
+    }
+
+    pub fn open_announcement_link(&self) {
+        let link = self
+            .selected_announcement()
+            .zip(self.announcement_links_state.selected())
+            .and_then(|(a, i)| a.links.get(i));
+        if let Some(link) = link {
+            let url = link.url.clone();
+            tokio::task::spawn(async move {
+                let _ = open::that(url);
+            });
+        }
+    }
+
+    pub fn toggle_archive(&mut self) {
+        self.mode = match self.mode {
+            Mode::Archive => Mode::Normal,
+            _ => {
+                let first = if self.data.archive.is_empty() { None } else { Some(0) };
+                self.archive_groups_state.select(first);
+                self.archive_state.select(first);
+                self.see_selected_archived();
+                Mode::Archive
+            }
+        };
+    }
+
+    pub fn next_archive_group(&mut self) {
+        if let Some(i) = self.archive_groups_state.selected()
+            && i + 1 < self.archive_groups().len()
+        {
+            self.archive_groups_state.select(Some(i + 1));
+            self.archive_state.select(Some(0));
+            self.see_selected_archived();
+        }
+    }
+
+    pub fn prev_archive_group(&mut self) {
+        if let Some(i) = self.archive_groups_state.selected() {
+            self.archive_groups_state.select(Some(i.saturating_sub(1)));
+            self.archive_state.select(Some(0));
+            self.see_selected_archived();
+        }
+    }
+
+    pub fn next_archived(&mut self) {
+        if let Some(i) = self.archive_state.selected()
+            && i + 1 < self.archive_selection().len()
+        {
+            self.archive_state.select(Some(i + 1));
+            self.see_selected_archived();
+        }
+    }
+
+    pub fn prev_archived(&mut self) {
+        if let Some(i) = self.archive_state.selected() {
+            self.archive_state.select(Some(i.saturating_sub(1)));
+            self.see_selected_archived();
+        }
+    }
+
+    pub async fn open_archived(&self) {
+        if let Some(i) = self.archive_state.selected()
+            && let Some(a) = self.archive_selection().get(i)
+            && !a.custom
+        {
+            let url = a.html_url.clone();
+            tokio::task::spawn(async move {
+                let _ = open::that(url);
+            });
+        }
     }
 
     pub fn on_tick(&mut self) {
-        ()
+        self.status.on_tick();
     }
 }
 
-pub async fn refresh(app: Arc<Mutex<App>>) -> Result<(), Box<dyn Error>> {
-    let app_clone = Arc::clone(&app);
+// Fetch the discussion topics of every course in the background
+pub async fn load_discussions(app: Arc<Mutex<App>>) {
+    let (client, course_ids) = {
+        let mut app = app.lock().await;
+        if app.settings.offline {
+            app.status.info("Offline mode, discussions aren't available".to_string());
+            return;
+        }
+        (app.client.clone(), app.data.course_ids())
+    };
     tokio::task::spawn(async move {
-        let course_ids = app.lock().await.course_ids.clone();
+        let fetched = crate::queries::discussions::query_discussions(&client, &course_ids).await;
+        let mut app = app.lock().await;
+        for (course_id, e) in &fetched.failures {
+            app.status.error(format!("Error fetching discussions for course {}: {}", course_id, e));
+        }
+        let mut discussions = fetched.items;
+        discussions.sort_by_cached_key(|d| (app.data.course_name(d.course_id), Reverse(d.active_at())));
+        let first = if discussions.is_empty() { None } else { Some(0) };
+        app.discussions = Some(discussions);
+        app.discussions_state.select(first);
+        app.select_pending_discussion();
+    });
+}
+
+// Fetch the discussions the first time their tab is opened
+pub async fn open_discussions(app: Arc<Mutex<App>>) {
+    let needed = {
+        let app = app.lock().await;
+        app.mode == Mode::Discussions && app.discussions.is_none()
+    };
+    if needed {
+        load_discussions(app).await;
+    }
+}
 
-        // query full list of assignments
-        let assignments = match crate::queries::assignments::query_assignments(&course_ids).await {
-            Ok(a) => a,
+// Open the selected topic and fetch its replies in the background
+pub async fn load_thread(app: Arc<Mutex<App>>) {
+    let (client, topic_id) = {
+        let mut app = app.lock().await;
+        let topic_id = match app.selected_discussion() {
+            Some(d) => d.id.clone(),
+            None => return,
+        };
+        app.mode = Mode::Thread;
+        app.thread_topic = Some(topic_id.clone());
+        app.thread = None;
+        (app.client.clone(), topic_id)
+    };
+    tokio::task::spawn(async move {
+        let entries = crate::queries::discussions::query_entries(&client, &topic_id).await;
+        let mut app = app.lock().await;
+        // The topic was closed or another one opened while fetching
+        if app.thread_topic.as_ref() != Some(&topic_id) {
+            return;
+        }
+        match entries {
+            Ok(entries) => {
+                let thread = discussion::thread(entries);
+                let first = if thread.is_empty() { None } else { Some(0) };
+                app.thread = Some(thread);
+                app.thread_state.select(first);
+            }
             Err(e) => {
-                eprintln!("Error fetching assignments: {}", e);
-                return;
+                app.status.error(format!("Error fetching discussion replies: {}", e));
+                app.thread = Some(vec![]);
             }
+        }
+    });
+}
+
+// Mark the selected entry of the open topic read, or all of them
+pub async fn read_entries(app: Arc<Mutex<App>>, all: bool) {
+    let (client, ids) = {
+        let mut app = app.lock().await;
+        let selected = app.thread_state.selected();
+        let topic_id = app.thread_topic.clone();
+        let client = app.client.clone();
+        let thread = match app.thread.as_mut() {
+            Some(thread) => thread,
+            None => return,
         };
+        let mut ids = vec![];
+        for (i, (_, entry)) in thread.iter_mut().enumerate() {
+            if !entry.read && (all || Some(i) == selected) {
+                entry.read = true;
+                ids.push(entry.id.clone());
+            }
+        }
+        let topic = app
+            .discussions
+            .as_mut()
+            .and_then(|d| d.iter_mut().find(|d| Some(&d.id) == topic_id.as_ref()));
+        if let Some(topic) = topic {
+            topic.unread = (topic.unread - ids.len() as i64).max(0);
+        }
+        if ids.is_empty() || app.settings.offline {
+            return;
+        }
+        (client, ids)
+    };
+    tokio::task::spawn(async move {
+        if let Err(e) = crate::queries::discussions::mark_entries_read(&client, ids).await {
+            app.lock().await.status.warn(format!("Could not mark replies read on Canvas: {}", e));
+        }
+    });
+}
 
-        // Store pre-refresh selected assignment hash
-        let selected_hash = app_clone.lock().await.get_selected_hash();
+// The user id is looked up once per session, the inbox can't be reached without it
+async fn user_id(app: &Arc<Mutex<App>>, client: &CanvasClient) -> Result<String, crate::queries::error::CanvasError> {
+    if let Some(id) = app.lock().await.user_id.clone() {
+        return Ok(id);
+    }
+    let id = crate::queries::conversations::query_user_id(client).await?;
+    app.lock().await.user_id = Some(id.clone());
+    Ok(id)
+}
 
-        app_clone.lock().await.data.update_assignments(assignments);
+// Fetch the inbox in the background
+pub async fn load_conversations(app: Arc<Mutex<App>>) {
+    let client = {
+        let mut app = app.lock().await;
+        if app.settings.offline {
+            app.status.info("Offline mode, the inbox isn't available".to_string());
+            return;
+        }
+        app.client.clone()
+    };
+    tokio::task::spawn(async move {
+        let conversations = match user_id(&app, &client).await {
+            Ok(user_id) => crate::queries::conversations::query_conversations(&client, &user_id).await,
+            Err(e) => Err(e),
+        };
+        let mut app = app.lock().await;
+        match conversations {
+            Ok(conversations) => {
+                // Keep the selection on the same conversation when reloading
+                let selected = app.selected_conversation().map(|c| c.id.clone());
+                let i = selected
+                    .and_then(|id| conversations.iter().position(|c| c.id == id))
+                    .or(if conversations.is_empty() { None } else { Some(0) });
+                app.conversations = Some(conversations);
+                app.conversations_state.select(i);
+            }
+            Err(e) => {
+                app.status.error(format!("Error fetching the inbox: {}", e));
+                if app.conversations.is_none() {
+                    app.conversations = Some(vec![]);
+                }
+            }
+        }
+    });
+}
 
-        // Restore selection to select pre-refresh hash
-        app_clone.lock().await.select_by_hash(selected_hash);
+// Fetch the inbox the first time its tab is opened
+pub async fn open_inbox(app: Arc<Mutex<App>>) {
+    let needed = {
+        let app = app.lock().await;
+        app.mode == Mode::Inbox && app.conversations.is_none()
+    };
+    if needed {
+        load_conversations(app).await;
+    }
+}
 
-        let grades = match crate::queries::grades::query_grades(&course_ids).await {
-            Ok(g) => g,
+// Open the selected conversation, fetch its messages and mark it read
+pub async fn load_messages(app: Arc<Mutex<App>>) {
+    let (client, id, unread) = {
+        let mut app = app.lock().await;
+        let (id, unread) = match app.selected_conversation() {
+            Some(c) => (c.id.clone(), c.unread),
+            None => return,
+        };
+        app.mode = Mode::Conversation;
+        app.open_conversation = Some(id.clone());
+        app.messages = None;
+        app.message_scroll = 0;
+        if let Some(c) = app.conversations.as_mut().and_then(|c| c.iter_mut().find(|c| c.id == id)) {
+            c.unread = false;
+        }
+        (app.client.clone(), id, unread)
+    };
+    if unread {
+        let (client, id, app) = (client.clone(), id.clone(), app.clone());
+        tokio::task::spawn(async move {
+            if let Err(e) = crate::queries::conversations::mark_read(&client, &id).await {
+                app.lock().await.status.warn(format!("Could not mark the conversation read on Canvas: {}", e));
+            }
+        });
+    }
+    tokio::task::spawn(async move {
+        let messages = crate::queries::conversations::query_messages(&client, &id).await;
+        let mut app = app.lock().await;
+        if app.open_conversation.as_ref() != Some(&id) {
+            return;
+        }
+        match messages {
+            Ok(messages) => app.messages = Some(messages),
             Err(e) => {
-                eprintln!("Error fetching grades: {}", e);
-                return;
+                app.status.error(format!("Error fetching messages: {}", e));
+                app.messages = Some(vec![]);
             }
+        }
+    });
+}
+
+// Fetch who can be messaged in the new message's course, unless that's known already
+pub async fn load_recipients(app: Arc<Mutex<App>>) {
+    let (client, course_id) = {
+        let app = app.lock().await;
+        match app.new_message_course() {
+            Some(id) if !app.recipients.contains_key(&id) && !app.settings.offline => (app.client.clone(), id),
+            _ => return,
+        }
+    };
+    tokio::task::spawn(async move {
+        let recipients = match user_id(&app, &client).await {
+            Ok(user_id) => crate::queries::conversations::query_recipients(&client, &user_id, course_id).await,
+            Err(e) => Err(e),
         };
-        app_clone.lock().await.data.remove_past_assignments();
-        app_clone.lock().await.data.grades = grades;
-        let path = app_clone.lock().await.data_path.clone();
-        match app_clone.lock().await.data.serialize_to_file(&path) {
-            Ok(_) => (),
-            Err(e) => {
-                eprintln!("Error saving data: {}", e);
+        let mut app = app.lock().await;
+        match recipients {
+            Ok(recipients) => {
+                app.recipients.insert(course_id, recipients);
+            }
+            Err(e) => app.status.error(format!("Error fetching recipients: {}", e)),
+        }
+    });
+}
+
+// A new message's file starts with a "Subject:" line, the rest is the body
+fn split_subject(text: &str) -> (String, String) {
+    match text.strip_prefix("Subject:") {
+        Some(rest) => {
+            let (subject, body) = rest.split_once('\n').unwrap_or((rest, ""));
+            (subject.trim().to_string(), body.trim().to_string())
+        }
+        None => (String::new(), text.trim().to_string()),
+    }
+}
+
+// Send what was written in the editor
+pub async fn send_composed(app: Arc<Mutex<App>>, compose: Compose, text: String) {
+    let client = app.lock().await.client.clone();
+    tokio::task::spawn(async move {
+        match compose {
+            Compose::Reply { topic_id, parent_id } => {
+                let res = crate::queries::discussions::create_entry(&client, &topic_id, parent_id, html::from_text(&text)).await;
+                match res {
+                    Ok(()) => {
+                        app.lock().await.status.info("Reply posted".to_string());
+                        // Show the new reply if the topic is still open
+                        let reopen = app.lock().await.thread_topic.as_ref() == Some(&topic_id);
+                        if reopen {
+                            load_thread(app.clone()).await;
+                        }
+                    }
+                    Err(e) => app.lock().await.status.error(format!("Error posting reply: {}", e)),
+                }
+            }
+            Compose::Message { conversation_id, recipients } => {
+                match crate::queries::conversations::add_message(&client, &conversation_id, recipients, text).await {
+                    Ok(()) => {
+                        app.lock().await.status.info("Message sent".to_string());
+                        let reopen = app.lock().await.open_conversation.as_ref() == Some(&conversation_id);
+                        if reopen {
+                            load_messages(app.clone()).await;
+                        }
+                    }
+                    Err(e) => app.lock().await.status.error(format!("Error sending message: {}", e)),
+                }
+            }
+            Compose::NewMessage { course_id, recipients } => {
+                let (subject, body) = split_subject(&text);
+                if body.is_empty() {
+                    app.lock().await.status.info("Nothing was written, not sending".to_string());
+                    return;
+                }
+                match crate::queries::conversations::create_conversation(&client, course_id, recipients, subject, body).await {
+                    Ok(()) => {
+                        app.lock().await.status.info("Message sent".to_string());
+                        load_conversations(app.clone()).await;
+                    }
+                    Err(e) => app.lock().await.status.error(format!("Error sending message: {}", e)),
+                }
+            }
+        }
+    });
+}
+
+// Mark the selected announcement read, here right away and on Canvas in the background
+pub async fn read_announcement(app: Arc<Mutex<App>>) {
+    let (client, course_id, id) = {
+        let mut app = app.lock().await;
+        let offline = app.settings.offline;
+        let client = app.client.clone();
+        let a = match app.announcements_state.selected().and_then(|i| app.data.announcements.get_mut(i)) {
+            Some(a) if !a.read => a,
+            _ => return,
+        };
+        a.read = true;
+        if offline {
+            return;
+        }
+        (client, a.course_id, a.id.clone())
+    };
+    tokio::task::spawn(async move {
+        if let Err(e) = crate::queries::announcements::mark_read(&client, course_id, &id).await {
+            app.lock().await.status.warn(format!("Could not mark announcement read on Canvas: {}", e));
+        }
+    });
+}
+
+pub async fn refresh(app: Arc<Mutex<App>>) -> Result<(), Box<dyn Error>> {
+    let started = {
+        let mut app = app.lock().await;
+        if app.settings.offline {
+            app.status.info("Offline mode, showing cached data".to_string());
+            return Ok(());
+        }
+        if app.status.is_refreshing() {
+            return Ok(());
+        }
+        app.status.start_refresh()
+    };
+
+    let watcher = app.clone();
+    let handle = tokio::task::spawn(async move {
+        let (profile, client, settings) = {
+            let app = app.lock().await;
+            (app.profile().clone(), app.client.clone(), app.settings.clone())
+        };
+        let mut errors: Vec<String> = vec![];
+        let mut notices: Vec<String> = vec![];
+
+        // Use the configured allowlist, otherwise discover the current term's courses
+        let course_ids = match &profile.course_ids {
+            Some(ids) => Some(ids.clone()),
+            None => {
+                let exclude = profile.exclude_course_ids.clone().unwrap_or_default();
+                match crate::queries::courses::discover_course_ids(&client, &exclude).await {
+                    Ok(ids) => Some(ids),
+                    Err(e) => {
+                        errors.push(format!("Error discovering courses: {}", e));
+                        None
+                    }
+                }
+            }
+        };
+
+        let mut app = if let Some(course_ids) = course_ids {
+            // query assignments, grades and announcements for every course at once
+            let (assignments, grades, announcements) = tokio::join!(
+                crate::queries::assignments::query_assignments(
+                    &client,
+                    &course_ids,
+                    settings.lookahead,
+                    settings.include_undated,
+                ),
+                crate::queries::grades::query_grades(&client, &course_ids),
+                crate::queries::announcements::query_announcements(&client, &course_ids),
+            );
+            for (course_id, e) in &assignments.failures {
+                errors.push(format!("Error fetching assignments for course {}: {}", course_id, e));
+            }
+            for (course_id, e) in &grades.failures {
+                errors.push(format!("Error fetching grades for course {}: {}", course_id, e));
+            }
+            for (course_id, e) in &announcements.failures {
+                errors.push(format!("Error fetching announcements for course {}: {}", course_id, e));
+            }
+
+            let mut app = app.lock().await;
+
+            // Drop the results if the profile was switched while querying
+            if app.status.refresh_started != Some(started) {
+                return;
+            }
+
+            // Store pre-refresh selected assignment id
+            let selected_id = app.get_selected_id();
+
+            // What was already known, to announce only what this refresh brought in
+            let flagged = app
+                .data
+                .assignments
+                .iter()
+                .chain(app.data.archive.iter())
+                .filter(|a| a.new_grade)
+                .map(|a| a.id.clone())
+                .collect::<Vec<String>>();
+            let old_grades = app.data.grades.iter().map(|g| (g.course_id, g.grade)).collect::<HashMap<u32, f64>>();
+
+            app.data.update_assignments(assignments.items);
+
+            // Restore selection to the pre-refresh assignment
+            app.select_by_id(selected_id);
+
+            let failed = grades.failures.iter().map(|f| f.0).collect::<Vec<u32>>();
+            let synced = course_ids
+                .iter()
+                .copied()
+                .filter(|id| {
+                    !failed.contains(id)
+                        && !assignments.failures.iter().any(|f| f.0 == *id)
+                        && !announcements.failures.iter().any(|f| f.0 == *id)
+                })
+                .collect::<Vec<u32>>();
+            app.data.archive_past_assignments(settings.overdue_for);
+            app.data.update_grades(grades.items, &failed);
+            app.solve_what_if();
+            app.data.mark_synced(&synced);
+            let unread = app.data.number_unread();
+            let failed_announcements = announcements.failures.iter().map(|f| f.0).collect::<Vec<u32>>();
+            app.data.update_announcements(announcements.items, &failed_announcements);
+            let new = app.data.number_unread().saturating_sub(unread);
+            if new > 0 {
+                notices.push(format!("{} new announcement(s)", new));
+            }
+
+            for a in app.data.assignments.iter().chain(app.data.archive.iter()) {
+                if a.new_grade && !flagged.contains(&a.id) {
+                    let score = a.score_text().or(a.grade.clone()).unwrap_or_default();
+                    notices.push(format!("New grade posted: {} - {} {}", a.course, a.name, score));
+                }
+            }
+            for g in &app.data.grades {
+                if let Some(old) = old_grades.get(&g.course_id)
+                    && *old != g.grade
+                {
+                    notices.push(format!("{} grade changed: {:.2}% -> {:.2}%", g.course, old, g.grade));
+                }
+            }
+            if let Err(e) = app.serialize_data() {
+                errors.push(format!("Error saving data: {}", e));
+            }
+            app
+        } else {
+            let app = app.lock().await;
+            if app.status.refresh_started != Some(started) {
                 return;
             }
+            app
         };
+
+        for notice in notices {
+            app.status.info(notice);
+        }
+        for warning in client.take_warnings() {
+            app.status.warn(warning);
+        }
+        for error in &errors {
+            app.status.error(error.clone());
+        }
+        app.status.finish_refresh(errors.len());
     });
-    
+
+    // A refresh that panicked never finished, which would block every later one
+    tokio::task::spawn(async move {
+        if let Err(e) = handle.await {
+            let mut app = watcher.lock().await;
+            if app.status.refresh_started == Some(started) {
+                app.status.error(format!("Refresh failed: {}", e));
+                app.status.finish_refresh(1);
+            }
+        }
+    });
+
     Ok(())
 }
@@ -0,0 +1,43 @@
+use crossterm::{
+    event::{DisableMouseCapture, EnableMouseCapture},
+    execute,
+    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+};
+use ratatui::{Terminal, backend::CrosstermBackend};
+use std::{env, error::Error, fs, io, io::Write, process::Command};
+
+// Hand the terminal to $VISUAL or $EDITOR on a file starting out as `template` and return
+// what was written, None if it was left empty
+pub fn compose(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, template: &str) -> Result<Option<String>, Box<dyn Error>> {
+    // A random name that must not exist yet, so nobody else on the machine can plant the
+    // file or a symlink there beforehand, readable only by the user
+    let path = env::temp_dir().join(format!("canvas-tui-{}.txt", uuid::Uuid::new_v4()));
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(&path)?.write_all(template.as_bytes())?;
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    // The variable may carry arguments, e.g. "code --wait"
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi").to_string();
+
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture)?;
+    // The editor blocks until it exits, which mustn't stall the runtime's other tasks
+    let status = tokio::task::block_in_place(|| Command::new(&program).args(words).arg(&path).status());
+    enable_raw_mode()?;
+    execute!(terminal.backend_mut(), EnterAlternateScreen, EnableMouseCapture)?;
+    terminal.clear()?;
+
+    let text = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+    let status = status.map_err(|e| format!("Could not start {}: {}", program, e))?;
+    if !status.success() {
+        return Err(format!("{} exited with {}", program, status).into());
+    }
+    let text = text?.trim().to_string();
+    Ok(if text.is_empty() { None } else { Some(text) })
+}
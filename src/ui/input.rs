@@ -1,15 +1,26 @@
 use crossterm::event::{self, Event, KeyEvent, KeyCode, KeyModifiers};
-use super::app::{self, App, Mode, AssignmentField};
+use super::app::{self, App, Mode, AssignmentField, MessageField, WhatIfField};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use std::error::Error;
 
 pub async fn handle_input(app: Arc<Mutex<App>>) -> Result<bool, Box<dyn Error>> {
     if let Event::Key(key) = event::read()? {
-        let mode = app.lock().await.mode.clone();
+        let mode = app.lock().await.mode;
         match mode {
             Mode::Normal => handle_input_normal(app.clone(), key).await,
             Mode::NewAssignment(_) => handle_input_new_assignment(app.clone(), key).await,
+            Mode::Log => handle_input_log(app.clone(), key).await,
+            Mode::Archive => handle_input_archive(app.clone(), key).await,
+            Mode::Grades | Mode::Breakdown => handle_input_grades(app.clone(), key).await,
+            Mode::WhatIf(_) => handle_input_what_if(app.clone(), key).await,
+            Mode::Term => handle_input_term(app.clone(), key).await,
+            Mode::Announcements => handle_input_announcements(app.clone(), key).await,
+            Mode::Discussions => handle_input_discussions(app.clone(), key).await,
+            Mode::Thread => handle_input_thread(app.clone(), key).await,
+            Mode::Inbox => handle_input_inbox(app.clone(), key).await,
+            Mode::Conversation => handle_input_conversation(app.clone(), key).await,
+            Mode::NewMessage(field) => handle_input_new_message(app.clone(), key, field).await,
         }
     } else {
         Ok(false)
@@ -31,8 +42,23 @@ async fn handle_input_normal(app: Arc<Mutex<App>>, key: KeyEvent) -> Result<bool
                 app::refresh(app.clone()).await?;
                 app.lock().await.serialize_data()?;
             },
+            KeyCode::Char('p') => {
+                let res = app.lock().await.next_profile();
+                match res {
+                    Ok(_) => app::refresh(app.clone()).await?,
+                    Err(e) => app.lock().await.status.error(format!("Error switching profile: {}", e)),
+                }
+            },
             KeyCode::Char('d') => app.lock().await.mark_done(),
+            KeyCode::Char('a') => app.lock().await.toggle_archive(),
+            KeyCode::Char('s') => app.lock().await.toggle_scores(),
+            KeyCode::Char('g') => app.lock().await.toggle_grades(),
+            KeyCode::Char('t') => app.lock().await.toggle_term(),
             KeyCode::Char('x') => app.lock().await.delete_assignment().await?,
+            KeyCode::Tab => {
+                app.lock().await.next_tab();
+                app::read_announcement(app.clone()).await;
+            }
             KeyCode::Enter => app.lock().await.enter(),
             KeyCode::Esc => app.lock().await.esc(),
             _ => (),
@@ -41,20 +67,242 @@ async fn handle_input_normal(app: Arc<Mutex<App>>, key: KeyEvent) -> Result<bool
             KeyCode::Char('J') => app.lock().await.next_link(),
             KeyCode::Char('K') => app.lock().await.prev_link(),
             KeyCode::Char('O') => app.lock().await.open_link().await,
+            KeyCode::Char('L') => app.lock().await.toggle_log(),
+            KeyCode::Char('D') => {
+                app.lock().await.show_discussion();
+                app::open_discussions(app.clone()).await;
+            }
             _ => (),
         },
-        KeyModifiers::CONTROL => match key.code {
-            KeyCode::Char('c') => {
+        KeyModifiers::CONTROL => {
+            if let KeyCode::Char('c') = key.code {
                 app.lock().await.serialize_data()?;
                 return Ok(true);
             }
-            _ => (),
         },
         _ => (),
     }
     Ok(false)
 }
 
+async fn handle_input_log(app: Arc<Mutex<App>>, key: KeyEvent) -> Result<bool, Box<dyn Error>> {
+    match key.code {
+        KeyCode::Char('j') | KeyCode::Down => app.lock().await.next_log_entry(),
+        KeyCode::Char('k') | KeyCode::Up => app.lock().await.prev_log_entry(),
+        KeyCode::Char('L') | KeyCode::Char('q') | KeyCode::Esc => app.lock().await.toggle_log(),
+        KeyCode::Char('c') if key.modifiers == KeyModifiers::CONTROL => {
+            app.lock().await.serialize_data()?;
+            return Ok(true);
+        }
+        _ => (),
+    }
+    Ok(false)
+}
+
+async fn handle_input_archive(app: Arc<Mutex<App>>, key: KeyEvent) -> Result<bool, Box<dyn Error>> {
+    match key.code {
+        KeyCode::Char('j') | KeyCode::Down => app.lock().await.next_archived(),
+        KeyCode::Char('k') | KeyCode::Up => app.lock().await.prev_archived(),
+        KeyCode::Char('l') | KeyCode::Right => app.lock().await.next_archive_group(),
+        KeyCode::Char('h') | KeyCode::Left => app.lock().await.prev_archive_group(),
+        KeyCode::Char('o') => app.lock().await.open_archived().await,
+        KeyCode::Char('a') | KeyCode::Char('q') | KeyCode::Esc => app.lock().await.toggle_archive(),
+        KeyCode::Char('c') if key.modifiers == KeyModifiers::CONTROL => {
+            app.lock().await.serialize_data()?;
+            return Ok(true);
+        }
+        _ => (),
+    }
+    Ok(false)
+}
+
+async fn handle_input_grades(app: Arc<Mutex<App>>, key: KeyEvent) -> Result<bool, Box<dyn Error>> {
+    let mode = app.lock().await.mode;
+    match key.code {
+        KeyCode::Char('j') | KeyCode::Down if mode == Mode::Grades => app.lock().await.next_grade(),
+        KeyCode::Char('k') | KeyCode::Up if mode == Mode::Grades => app.lock().await.prev_grade(),
+        KeyCode::Enter => app.lock().await.toggle_breakdown(),
+        KeyCode::Char('w') if mode == Mode::Breakdown => app.lock().await.toggle_what_if(),
+        KeyCode::Esc | KeyCode::Char('q') if mode == Mode::Breakdown => app.lock().await.toggle_breakdown(),
+        KeyCode::Char('g') | KeyCode::Char('q') | KeyCode::Esc => app.lock().await.toggle_grades(),
+        KeyCode::Char('c') if key.modifiers == KeyModifiers::CONTROL => {
+            app.lock().await.serialize_data()?;
+            return Ok(true);
+        }
+        _ => (),
+    }
+    Ok(false)
+}
+
+async fn handle_input_what_if(app: Arc<Mutex<App>>, key: KeyEvent) -> Result<bool, Box<dyn Error>> {
+    let mode = app.lock().await.mode;
+    match key.code {
+        KeyCode::Char('c') if key.modifiers == KeyModifiers::CONTROL => {
+            app.lock().await.serialize_data()?;
+            return Ok(true);
+        }
+        KeyCode::Char(c) if c.is_ascii_digit() || c == '.' => app.lock().await.what_if_push(c),
+        KeyCode::Backspace => app.lock().await.what_if_pop(),
+        KeyCode::Enter | KeyCode::Esc if mode == Mode::WhatIf(WhatIfField::Target) => app.lock().await.toggle_what_if_target(),
+        KeyCode::Char('j') | KeyCode::Down => app.lock().await.next_what_if(),
+        KeyCode::Char('k') | KeyCode::Up => app.lock().await.prev_what_if(),
+        KeyCode::Char('t') => app.lock().await.toggle_what_if_target(),
+        KeyCode::Char('w') | KeyCode::Char('q') | KeyCode::Esc => app.lock().await.toggle_what_if(),
+        _ => (),
+    }
+    Ok(false)
+}
+
+async fn handle_input_term(app: Arc<Mutex<App>>, key: KeyEvent) -> Result<bool, Box<dyn Error>> {
+    match key.code {
+        KeyCode::Char('t') | KeyCode::Char('q') | KeyCode::Esc => app.lock().await.toggle_term(),
+        KeyCode::Char('c') if key.modifiers == KeyModifiers::CONTROL => {
+            app.lock().await.serialize_data()?;
+            return Ok(true);
+        }
+        _ => (),
+    }
+    Ok(false)
+}
+
+async fn handle_input_announcements(app: Arc<Mutex<App>>, key: KeyEvent) -> Result<bool, Box<dyn Error>> {
+    match (key.modifiers, key.code) {
+        (KeyModifiers::CONTROL, KeyCode::Char('c')) => {
+            app.lock().await.serialize_data()?;
+            return Ok(true);
+        }
+        (KeyModifiers::CONTROL, KeyCode::Char('d')) | (_, KeyCode::PageDown) => app.lock().await.scroll_announcement(true),
+        (KeyModifiers::CONTROL, KeyCode::Char('u')) | (_, KeyCode::PageUp) => app.lock().await.scroll_announcement(false),
+        (_, KeyCode::Char('j') | KeyCode::Down) => {
+            app.lock().await.next_announcement();
+            app::read_announcement(app.clone()).await;
+        }
+        (_, KeyCode::Char('k') | KeyCode::Up) => {
+            app.lock().await.prev_announcement();
+            app::read_announcement(app.clone()).await;
+        }
+        (_, KeyCode::Char('J')) => app.lock().await.next_announcement_link(),
+        (_, KeyCode::Char('K')) => app.lock().await.prev_announcement_link(),
+        (_, KeyCode::Char('O')) => app.lock().await.open_announcement_link(),
+        (_, KeyCode::Char('o')) => app.lock().await.open_announcement(),
+        (_, KeyCode::Tab) => {
+            app.lock().await.next_tab();
+            app::open_discussions(app.clone()).await;
+        }
+        (_, KeyCode::Esc | KeyCode::Char('q')) => app.lock().await.mode = Mode::Normal,
+        _ => (),
+    }
+    Ok(false)
+}
+
+async fn handle_input_discussions(app: Arc<Mutex<App>>, key: KeyEvent) -> Result<bool, Box<dyn Error>> {
+    match (key.modifiers, key.code) {
+        (KeyModifiers::CONTROL, KeyCode::Char('c')) => {
+            app.lock().await.serialize_data()?;
+            return Ok(true);
+        }
+        (_, KeyCode::Char('j') | KeyCode::Down) => app.lock().await.next_discussion(),
+        (_, KeyCode::Char('k') | KeyCode::Up) => app.lock().await.prev_discussion(),
+        (_, KeyCode::Enter) => app::load_thread(app.clone()).await,
+        (_, KeyCode::Char('o')) => app.lock().await.open_discussion(),
+        (_, KeyCode::Char('r')) => app::load_discussions(app.clone()).await,
+        (_, KeyCode::Tab) => {
+            app.lock().await.next_tab();
+            app::open_inbox(app.clone()).await;
+        }
+        (_, KeyCode::Esc | KeyCode::Char('q')) => app.lock().await.mode = Mode::Normal,
+        _ => (),
+    }
+    Ok(false)
+}
+
+async fn handle_input_thread(app: Arc<Mutex<App>>, key: KeyEvent) -> Result<bool, Box<dyn Error>> {
+    match (key.modifiers, key.code) {
+        (KeyModifiers::CONTROL, KeyCode::Char('c')) => {
+            app.lock().await.serialize_data()?;
+            return Ok(true);
+        }
+        (_, KeyCode::Char('j') | KeyCode::Down) => app.lock().await.next_entry(),
+        (_, KeyCode::Char('k') | KeyCode::Up) => app.lock().await.prev_entry(),
+        (_, KeyCode::Char('m')) => app::read_entries(app.clone(), false).await,
+        (_, KeyCode::Char('M')) => app::read_entries(app.clone(), true).await,
+        (_, KeyCode::Char('r')) => app.lock().await.reply(true),
+        (_, KeyCode::Char('R')) => app.lock().await.reply(false),
+        (_, KeyCode::Char('o')) => app.lock().await.open_discussion(),
+        (_, KeyCode::Esc | KeyCode::Char('q')) => app.lock().await.close_thread(),
+        _ => (),
+    }
+    Ok(false)
+}
+
+async fn handle_input_inbox(app: Arc<Mutex<App>>, key: KeyEvent) -> Result<bool, Box<dyn Error>> {
+    match (key.modifiers, key.code) {
+        (KeyModifiers::CONTROL, KeyCode::Char('c')) => {
+            app.lock().await.serialize_data()?;
+            return Ok(true);
+        }
+        (_, KeyCode::Char('j') | KeyCode::Down) => app.lock().await.next_conversation(),
+        (_, KeyCode::Char('k') | KeyCode::Up) => app.lock().await.prev_conversation(),
+        (_, KeyCode::Enter) => app::load_messages(app.clone()).await,
+        (_, KeyCode::Char('n')) => {
+            app.lock().await.start_new_message();
+            app::load_recipients(app.clone()).await;
+        }
+        (_, KeyCode::Char('r')) => app::load_conversations(app.clone()).await,
+        (_, KeyCode::Tab | KeyCode::Esc | KeyCode::Char('q')) => app.lock().await.next_tab(),
+        _ => (),
+    }
+    Ok(false)
+}
+
+async fn handle_input_conversation(app: Arc<Mutex<App>>, key: KeyEvent) -> Result<bool, Box<dyn Error>> {
+    match (key.modifiers, key.code) {
+        (KeyModifiers::CONTROL, KeyCode::Char('c')) => {
+            app.lock().await.serialize_data()?;
+            return Ok(true);
+        }
+        (KeyModifiers::CONTROL, KeyCode::Char('d')) | (_, KeyCode::PageDown | KeyCode::Char('j') | KeyCode::Down) => {
+            app.lock().await.scroll_messages(true)
+        }
+        (KeyModifiers::CONTROL, KeyCode::Char('u')) | (_, KeyCode::PageUp | KeyCode::Char('k') | KeyCode::Up) => {
+            app.lock().await.scroll_messages(false)
+        }
+        (_, KeyCode::Char('r')) => app.lock().await.reply_to_conversation(),
+        (_, KeyCode::Esc | KeyCode::Char('q')) => app.lock().await.close_conversation(),
+        _ => (),
+    }
+    Ok(false)
+}
+
+async fn handle_input_new_message(app: Arc<Mutex<App>>, key: KeyEvent, field: MessageField) -> Result<bool, Box<dyn Error>> {
+    match (key.modifiers, key.code, field) {
+        (KeyModifiers::CONTROL, KeyCode::Char('c'), _) => {
+            app.lock().await.serialize_data()?;
+            return Ok(true);
+        }
+        (_, KeyCode::Esc, _) => app.lock().await.cancel_new_message(),
+        (_, KeyCode::Tab | KeyCode::BackTab, _) => app.lock().await.toggle_message_field(),
+        (_, KeyCode::Char('j' | 'l') | KeyCode::Down | KeyCode::Right, MessageField::Course) => {
+            app.lock().await.cycle_message_course(true);
+            app::load_recipients(app.clone()).await;
+        }
+        (_, KeyCode::Char('k' | 'h') | KeyCode::Up | KeyCode::Left, MessageField::Course) => {
+            app.lock().await.cycle_message_course(false);
+            app::load_recipients(app.clone()).await;
+        }
+        (_, KeyCode::Enter, MessageField::Course) => app.lock().await.toggle_message_field(),
+        (_, KeyCode::Down, MessageField::Recipients) => app.lock().await.next_suggestion(),
+        (_, KeyCode::Up, MessageField::Recipients) => app.lock().await.prev_suggestion(),
+        (_, KeyCode::Enter, MessageField::Recipients) => app.lock().await.recipient_enter(),
+        (_, KeyCode::Backspace, MessageField::Recipients) => app.lock().await.recipient_pop(),
+        (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c), MessageField::Recipients) => {
+            app.lock().await.recipient_push(c)
+        }
+        _ => (),
+    }
+    Ok(false)
+}
+
 async fn handle_input_new_assignment(app: Arc<Mutex<App>>, key: KeyEvent) -> Result<bool, Box<dyn Error>> {
     match key.modifiers {
         KeyModifiers::NONE => match key.code {
@@ -62,9 +310,10 @@ async fn handle_input_new_assignment(app: Arc<Mutex<App>>, key: KeyEvent) -> Res
             _ => take_new_assignment_input(app.clone(), key).await,
         }
         KeyModifiers::SHIFT => take_new_assignment_input(app.clone(), key).await,
-        KeyModifiers::CONTROL => match key.code {
-            KeyCode::Char('c') => app.lock().await.exit_new_assignment_mode().await?,
-            _ => (),
+        KeyModifiers::CONTROL => {
+            if let KeyCode::Char('c') = key.code {
+                app.lock().await.exit_new_assignment_mode().await?
+            }
         }
         _ => (),
     };
@@ -74,9 +323,8 @@ async fn handle_input_new_assignment(app: Arc<Mutex<App>>, key: KeyEvent) -> Res
 async fn take_new_assignment_input(app: Arc<Mutex<App>>, key: KeyEvent) {
     let mut app = app.lock().await;
 
-    let field = match app.mode {
-        Mode::Normal => unreachable!(),
-        Mode::NewAssignment(field) => field,
+    let Mode::NewAssignment(field) = app.mode else {
+        unreachable!()
     };
 
     let i = app.assignments_state.selected().unwrap();
@@ -125,9 +373,10 @@ async fn take_new_assignment_input(app: Arc<Mutex<App>>, key: KeyEvent) {
             KeyCode::Char(c) => text.push(c),
             _ => (),
         },
-        KeyModifiers::SHIFT => match key.code {
-            KeyCode::Char(c) => text.push(c),
-            _ => (),
+        KeyModifiers::SHIFT => {
+            if let KeyCode::Char(c) = key.code {
+                text.push(c)
+            }
         },
         _ => (),
     }
@@ -1,9 +1,19 @@
 mod app;
+mod editor;
 mod input;
+pub mod picker;
+mod status;
 
+use crate::config::{Profile, Settings};
+use crate::queries::client::CanvasClient;
+use crate::storage::Storage;
+use crate::types::assignment::Assignment;
+use crate::types::grade::SOLVE_MAX_FACTOR;
 use crate::types::data::Data;
+use crate::types::submission::SubmissionState;
 use self::input::handle_input;
-use app::{App, Mode, AssignmentField};
+use app::{App, Mode, AssignmentField, MessageField, WhatIfField};
+use status::LogLevel;
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture},
     execute,
@@ -19,38 +29,94 @@ use tokio::sync::Mutex;
 use ratatui::{
     Terminal,
     backend::{Backend, CrosstermBackend},
-    layout::{Alignment, Constraint, Direction, Layout},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
+    text::{Line, Span},
     widgets::{
-        Block, Borders, Cell, Paragraph, Row,
-        Table, List, ListItem, Wrap,
+        Block, Borders, Cell, Clear, Paragraph, Row,
+        Table, TableState, List, ListItem, ListState, Wrap,
     },
 };
 
-async fn render_assignments(app: Arc<Mutex<App>>) -> Table<'static> {
+// Icon and color for each Canvas submission state
+fn submission_style(state: SubmissionState) -> (&'static str, Color) {
+    match state {
+        SubmissionState::Unsubmitted => ("○", Color::Gray),
+        SubmissionState::Submitted => ("✓", Color::LightBlue),
+        SubmissionState::Graded => ("★", Color::Green),
+        SubmissionState::Late => ("◷", Color::Yellow),
+        SubmissionState::Missing => ("✗", Color::Red),
+        SubmissionState::Excused => ("–", Color::DarkGray),
+        SubmissionState::ResubmissionNeeded => ("↻", Color::Magenta),
+    }
+}
+
+// Custom assignments have nothing on Canvas to show
+fn submission_cell(a: &Assignment) -> Cell<'static> {
+    if a.custom {
+        return Cell::from("");
+    }
+    let (icon, color) = submission_style(a.submission);
+    // Graded since the user last selected it
+    if a.new_grade {
+        return Cell::from(format!("{} {} (new)", icon, a.submission.label()))
+            .style(Style::default().fg(Color::LightCyan).add_modifier(Modifier::BOLD));
+    }
+    Cell::from(format!("{} {}", icon, a.submission.label())).style(Style::default().fg(color))
+}
+
+// Text sparkline of the last few snapshots, scaled between their lowest and highest grade
+fn sparkline(grades: &[f64]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let grades = &grades[grades.len().saturating_sub(10)..];
+    if grades.len() < 2 {
+        return String::new();
+    }
+    let min = grades.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = grades.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    grades
+        .iter()
+        .map(|g| {
+            if max <= min {
+                BARS[3]
+            } else {
+                BARS[((g - min) / (max - min) * 7.0).round() as usize]
+            }
+        })
+        .collect()
+}
+
+// Either the Overdue section (the first `overdue` assignments) or the upcoming ones after it
+async fn render_assignments(app: Arc<Mutex<App>>, overdue: bool) -> Table<'static> {
     let app = app.lock().await;
+    let n_overdue = app.data.number_overdue();
+    let section = if overdue {
+        &app.data.assignments[..n_overdue]
+    } else {
+        &app.data.assignments[n_overdue..]
+    };
     let bold = Style::default()
         .fg(Color::White)
         .add_modifier(Modifier::BOLD);
-    let mut header_cells: Vec<Cell> = ["Course", "Name", "Due Date"]
+    let mut header_cells: Vec<Cell> = ["Course", "Name", "Due Date", "Status"]
         .iter()
         .map(|h| Cell::from(*h))
         .collect();
+    if app.show_scores {
+        header_cells.push(Cell::from("Score"));
+    }
 
     // If creating a new assignment highlight the property being modified
-    match app.mode {
-        Mode::NewAssignment(field) => {
-            match field {
-                AssignmentField::Course => header_cells[0] = Cell::from("Course").style(Style::default().fg(Color::LightYellow)),
-                AssignmentField::Name => header_cells[1] = Cell::from("Name").style(Style::default().fg(Color::LightYellow)),
-                AssignmentField::DueDate => header_cells[2] = Cell::from("Due Date").style(Style::default().fg(Color::LightYellow)),
-            }
-        },
-        _ => (),
+    if let Mode::NewAssignment(field) = app.mode {
+        match field {
+            AssignmentField::Course => header_cells[0] = Cell::from("Course").style(Style::default().fg(Color::LightYellow)),
+            AssignmentField::Name => header_cells[1] = Cell::from("Name").style(Style::default().fg(Color::LightYellow)),
+            AssignmentField::DueDate => header_cells[2] = Cell::from("Due Date").style(Style::default().fg(Color::LightYellow)),
+        }
     }
 
     let header = Row::new(header_cells).style(bold).height(1);
-    let rows = app.data.assignments.iter().map(|a| {
+    let rows = section.iter().map(|a| {
         let date = if let Some(date) = a.date {
             date.format("%A %-d, %H:%M").to_string()
         } else {
@@ -61,71 +127,420 @@ async fn render_assignments(app: Arc<Mutex<App>>) -> Table<'static> {
         } else {
             a.name.clone()
         };
-        let cells = vec![
-            format!("{}", a.course),
-            name,
-            format!("{}", date),
+        // Flag courses whose cached data is older than stale_after_hours
+        let course = if app.is_stale(a.course_id) {
+            Cell::from(format!("{} ⚠", a.course)).style(Style::default().fg(Color::Yellow))
+        } else {
+            Cell::from(a.course.clone())
+        };
+        let mut cells = vec![
+            course,
+            Cell::from(name),
+            Cell::from(date),
+            submission_cell(a),
         ];
+        if app.show_scores {
+            cells.push(Cell::from(a.score_text().unwrap_or_default()));
+        }
         let style = if a.completed {
             Style::default()
                 .fg(Color::DarkGray)
                 .add_modifier(Modifier::CROSSED_OUT)
+        } else if overdue {
+            Style::default().fg(Color::Red)
         } else {
             Style::default()
         };
         Row::new(cells).style(style)
-    });
+    }).collect::<Vec<_>>();
+    let block = if overdue {
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Red))
+            .title(format!("Overdue ({})", n_overdue))
+    } else {
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Upcoming Assignments")
+    };
     let selected_style = match app.mode {
         Mode::Normal => Style::default().fg(Color::LightGreen).add_modifier(Modifier::BOLD),
         Mode::NewAssignment(_) => Style::default().fg(Color::LightYellow).add_modifier(Modifier::BOLD),
+        Mode::Log | Mode::Archive | Mode::Grades | Mode::Breakdown | Mode::WhatIf(_) | Mode::Term | Mode::Announcements
+        | Mode::Discussions | Mode::Thread | Mode::Inbox | Mode::Conversation | Mode::NewMessage(_) => Style::default().add_modifier(Modifier::BOLD),
     };
-    let table = Table::default()
+
+    let widths = if app.show_scores {
+        vec![
+            Constraint::Ratio(1, 10),
+            Constraint::Ratio(4, 10),
+            Constraint::Ratio(2, 10),
+            Constraint::Ratio(2, 10),
+            Constraint::Ratio(1, 10),
+        ]
+    } else {
+        vec![
+            Constraint::Ratio(1, 10),
+            Constraint::Ratio(5, 10),
+            Constraint::Ratio(2, 10),
+            Constraint::Ratio(2, 10),
+        ]
+    };
+
+    Table::default()
+        .rows(rows)
+        .header(header)
+        .block(block)
+        .row_highlight_style(selected_style)
+        .widths(widths)
+}
+
+async fn render_grades(app: Arc<Mutex<App>>) -> Table<'static> {
+    let app = app.lock().await;
+    let bold = Style::default()
+        .fg(Color::White)
+        .add_modifier(Modifier::BOLD);
+    let header_cells = ["Course", "Grade", "Up", "Down", "Trend"].iter().map(|h| Cell::from(*h));
+    let header = Row::new(header_cells).style(bold).height(1);
+    let rows = app.data.grades.iter().map(|g| {
+        let style = if g.change().is_some() {
+            Style::default().fg(Color::LightCyan).add_modifier(Modifier::BOLD)
+        } else if app.is_stale(Some(g.course_id)) {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default()
+        };
+        let mut grade = match g.letter() {
+            Some(letter) => format!("{:.2}% {}", g.grade, letter),
+            None => format!("{:.2}%", g.grade),
+        };
+        // Movement since the user last selected the course
+        match g.change() {
+            Some(change) if change > 0.0 => grade.push_str(&format!(" ▲{:.2}", change)),
+            Some(change) => grade.push_str(&format!(" ▼{:.2}", -change)),
+            None => (),
+        }
+        let history = g.history.iter().map(|s| s.grade).collect::<Vec<f64>>();
+        // Percentage points to the neighbouring cutoffs
+        let up = g.next_cutoff_up().map_or("-".to_string(), |(c, d)| format!("+{:.2} {}", d, c.name));
+        let down = g.next_cutoff_down().map_or("-".to_string(), |(c, d)| format!("-{:.2} {}", d, c.name));
+        let cells = vec![
+            Cell::from(g.course.clone()),
+            Cell::from(grade),
+            Cell::from(up).style(Style::default().fg(Color::Green)),
+            Cell::from(down).style(Style::default().fg(Color::Red)),
+            Cell::from(sparkline(&history)),
+        ];
+        Row::new(cells).style(style)
+    });
+
+    let block = if app.mode == Mode::Grades {
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::LightGreen))
+            .title("Grades (Enter: breakdown, g/Esc: back)")
+    } else {
+        Block::default().borders(Borders::ALL).title("Grades (g)")
+    };
+    let selected_style = match app.mode {
+        Mode::Grades | Mode::Breakdown | Mode::WhatIf(_) => Style::default().fg(Color::LightGreen).add_modifier(Modifier::BOLD),
+        _ => Style::default(),
+    };
+
+    Table::default()
+        .rows(rows)
+        .header(header)
+        .block(block)
+        .row_highlight_style(selected_style)
+        .widths([
+            Constraint::Ratio(6, 20),
+            Constraint::Ratio(5, 20),
+            Constraint::Ratio(3, 20),
+            Constraint::Ratio(3, 20),
+            Constraint::Ratio(3, 20),
+        ])
+}
+
+async fn render_breakdown(app: Arc<Mutex<App>>) -> Table<'static> {
+    let app = app.lock().await;
+    let bold = Style::default()
+        .fg(Color::White)
+        .add_modifier(Modifier::BOLD);
+    let grade = match app.selected_grade() {
+        Some(g) => g,
+        None => return Table::default(),
+    };
+    let header_cells = ["Group", "Score", "Weight", "Counts For"].iter().map(|h| Cell::from(*h));
+    let header = Row::new(header_cells).style(bold).height(1);
+    let percent = |p: Option<f64>| p.map_or("-".to_string(), |p| format!("{:.2}%", p));
+
+    // In what-if mode the hypothetical scores count like real ones
+    let what_if = matches!(app.mode, Mode::WhatIf(_));
+    let grade = if what_if {
+        grade.with_scores(&app.what_if_scores())
+    } else {
+        grade.clone()
+    };
+    let breakdown = grade.breakdown();
+    let mut rows = breakdown.iter().map(|g| {
+        let name = if g.dropped > 0 {
+            format!("{} ({} graded, {} dropped)", g.name, g.graded, g.dropped)
+        } else {
+            format!("{} ({} graded)", g.name, g.graded)
+        };
+        let score = match g.percent() {
+            Some(p) => format!("{:.2}% ({} / {})", p, g.score, g.possible),
+            None => "No graded work".to_string(),
+        };
+        let style = if g.percent().is_some() {
+            Style::default()
+        } else {
+            Style::default().fg(Color::DarkGray)
+        };
+        Row::new(vec![
+            Cell::from(name),
+            Cell::from(score),
+            Cell::from(g.weight.map_or("-".to_string(), |w| format!("{}%", w))),
+            Cell::from(percent(g.contribution)),
+        ])
+        .style(style)
+    }).collect::<Vec<_>>();
+
+    let total: f64 = breakdown.iter().filter_map(|g| g.contribution).sum();
+    let letter = |percent: f64| grade.cutoff_for(percent).map_or(String::new(), |c| format!(" {}", c.name));
+    rows.push(
+        Row::new(vec![
+            Cell::from("Total"),
+            Cell::from(if what_if {
+                format!("{:.2}%{} projected (now {:.2}%)", total, letter(total), grade.grade)
+            } else {
+                format!("{:.2}%{} (Canvas: {:.2}%)", total, letter(total), grade.grade)
+            }),
+            Cell::from(if grade.weighted { "weighted" } else { "by points" }),
+            Cell::from(format!("{:.2}%", total)),
+        ])
+        .style(bold),
+    );
+    // Canvas's grade counting unsubmitted work as zero
+    if let Some(final_grade) = &grade.final_grade {
+        rows.push(Row::new(vec![Cell::from("Final grade"), Cell::from(final_grade.clone())]).style(bold));
+    }
+
+    Table::default()
         .rows(rows)
         .header(header)
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .title("Upcoming Assignments"),
+                .title(if what_if {
+                    format!("{} what-if breakdown", grade.course)
+                } else {
+                    format!("{} breakdown (w: what-if, Esc: close)", grade.course)
+                }),
         )
-        .row_highlight_style(selected_style)
-        .widths(&[
+        .widths([
+            Constraint::Ratio(4, 10),
+            Constraint::Ratio(3, 10),
             Constraint::Ratio(1, 10),
-            Constraint::Ratio(6, 10),
+            Constraint::Ratio(2, 10),
+        ])
+}
+
+async fn render_what_if(app: Arc<Mutex<App>>) -> (Table<'static>, Paragraph<'static>) {
+    let app = app.lock().await;
+    let bold = Style::default()
+        .fg(Color::White)
+        .add_modifier(Modifier::BOLD);
+    let header_cells = ["Group", "Assignment", "Possible", "What-if"].iter().map(|h| Cell::from(*h));
+    let header = Row::new(header_cells).style(bold).height(1);
+
+    let rows = app.what_if_assignments().into_iter().map(|(group, a)| {
+        let score = app.what_if.get(&a.id).cloned().unwrap_or_default();
+        let style = if score.is_empty() {
+            Style::default().fg(Color::DarkGray)
+        } else if score.parse::<f64>().is_err() {
+            Style::default().fg(Color::Red)
+        } else {
+            Style::default()
+        };
+        Row::new(vec![
+            Cell::from(group),
+            Cell::from(a.name),
+            Cell::from(a.points_possible.to_string()),
+            Cell::from(if score.is_empty() { "-".to_string() } else { score }),
+        ])
+        .style(style)
+    }).collect::<Vec<_>>();
+    let editing_target = app.mode == Mode::WhatIf(WhatIfField::Target);
+    let highlight = if editing_target {
+        Style::default().add_modifier(Modifier::BOLD)
+    } else {
+        Style::default().fg(Color::LightGreen).add_modifier(Modifier::BOLD)
+    };
+    let table = Table::default()
+        .rows(rows)
+        .header(header)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Ungraded (type a score, t: target, Esc: back)"),
+        )
+        .row_highlight_style(highlight)
+        .widths([
             Constraint::Ratio(3, 10),
+            Constraint::Ratio(4, 10),
+            Constraint::Ratio(1, 10),
+            Constraint::Ratio(2, 10),
         ]);
-    return table;
+
+    let solution = match app.what_if_solution.clone() {
+        Some((a, target, Some(needed))) if needed > a.points_possible => Line::styled(
+            format!(
+                "Need at least {} / {} on {} for {}%, only possible with extra credit",
+                needed, a.points_possible, a.name, target
+            ),
+            Style::default().fg(Color::Yellow),
+        ),
+        Some((a, target, Some(needed))) => Line::from(format!(
+            "Need at least {} / {} on {} for {}%",
+            needed, a.points_possible, a.name, target
+        )),
+        Some((a, target, None)) => Line::styled(
+            format!(
+                "{}% is out of reach, even with {}x the points possible on {}",
+                target, SOLVE_MAX_FACTOR, a.name
+            ),
+            Style::default().fg(Color::Red),
+        ),
+        None => Line::styled("Set a target to solve for the selected assignment", Style::default().fg(Color::DarkGray)),
+    };
+    let target_style = if editing_target {
+        Style::default().fg(Color::LightGreen)
+    } else {
+        Style::default()
+    };
+    let paragraph = Paragraph::new(solution).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(target_style)
+            .title(format!("Target: {}%", app.what_if_target)),
+    );
+    (table, paragraph)
 }
 
-async fn render_grades(app: Arc<Mutex<App>>) -> Table<'static> {
+async fn render_term(app: Arc<Mutex<App>>) -> (Table<'static>, Paragraph<'static>) {
     let app = app.lock().await;
     let bold = Style::default()
         .fg(Color::White)
         .add_modifier(Modifier::BOLD);
-    let header_cells = ["Course", "Grade"].iter().map(|h| Cell::from(*h));
+    let summary = app.gpa_summary();
+    let header_cells = ["Course", "Grade", "Credits", "Points"].iter().map(|h| Cell::from(*h));
     let header = Row::new(header_cells).style(bold).height(1);
-    let rows = app.data.grades.iter().map(|g| {
-        let cells = vec![format!("{}", g.course), format!("{}", g.grade)];
-        Row::new(cells)
-    });
+    let rows = summary.courses.iter().map(|c| {
+        let grade = match &c.letter {
+            Some(letter) => format!("{:.2}% {}", c.percent, letter),
+            None => format!("{:.2}%", c.percent),
+        };
+        let style = if c.counts() {
+            Style::default()
+        } else {
+            Style::default().fg(Color::DarkGray)
+        };
+        Row::new(vec![
+            Cell::from(c.course.clone()),
+            Cell::from(grade),
+            Cell::from(c.credits.map_or("-".to_string(), |c| c.to_string())),
+            Cell::from(c.points.map_or("-".to_string(), |p| format!("{:.1}", p))),
+        ])
+        .style(style)
+    }).collect::<Vec<_>>();
     let table = Table::default()
         .rows(rows)
         .header(header)
-        .block(Block::default().borders(Borders::ALL).title("Grades"))
-        .widths(&[Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)]);
-    return table;
+        .block(Block::default().borders(Borders::ALL).title("Term summary (t/Esc: close)"))
+        .widths([
+            Constraint::Ratio(4, 10),
+            Constraint::Ratio(2, 10),
+            Constraint::Ratio(2, 10),
+            Constraint::Ratio(2, 10),
+        ]);
+
+    let gpa = |gpa: Option<f64>| gpa.map_or("-".to_string(), |g| format!("{:.2}", g));
+    let mut lines = vec![Line::from(format!(
+        "Term GPA: {} ({} credits)",
+        gpa(summary.term_gpa),
+        summary.term_credits
+    ))];
+    if summary.prior_gpa.is_some() {
+        lines.push(Line::from(format!(
+            "Prior GPA: {} ({} credits)",
+            gpa(summary.prior_gpa),
+            summary.prior_credits
+        )));
+    }
+    lines.push(Line::styled(format!("Cumulative GPA: {}", gpa(summary.cumulative_gpa)), bold));
+    if summary.courses.iter().any(|c| !c.counts()) {
+        lines.push(Line::styled(
+            "Greyed out courses have no credits in config.toml or no letter grade",
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    let paragraph = Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("GPA (projected)"));
+    (table, paragraph)
 }
 
 async fn render_welcome(app: Arc<Mutex<App>>) -> Paragraph<'static> {
     let app = app.lock().await;
-    Paragraph::new(format!(
+    let title = if app.profiles.len() > 1 {
+        format!("Welcome to CanvasTUI [{}]", app.profile().name)
+    } else {
+        "Welcome to CanvasTUI".to_string()
+    };
+
+    // Canvas rate limit budget, only known after the first request
+    let rate_limit = app.client.rate_limit();
+    let rate_limit_line = match rate_limit.remaining {
+        Some(remaining) => {
+            let (text, color) = if rate_limit.is_throttled() {
+                (format!(" Throttled by Canvas, waiting ({:.0} left) ", remaining), Color::Red)
+            } else if rate_limit.is_low() {
+                (format!(" API budget low: {:.0}, slowing down ", remaining), Color::Yellow)
+            } else {
+                (format!(" API budget: {:.0} ", remaining), Color::DarkGray)
+            };
+            Line::from(Span::styled(text, Style::default().fg(color))).right_aligned()
+        }
+        None => Line::default(),
+    };
+
+    let mut text = format!(
         "\nToday is {}, there are {} upcoming assignments",
         chrono::Local::now().format("%A %B %-d"),
         app.data.get_number_incomplete()
-    ))
+    );
+    let mut course_ids = app.data.course_ids();
+    course_ids.retain(|id| app.is_stale(Some(*id)));
+    let stale = course_ids.len();
+    if app.settings.offline {
+        text.push_str(" (offline, showing cached data)");
+    }
+    if stale > 0 {
+        text.push_str(&format!(" ⚠ {} course(s) out of date", stale));
+    }
+    let unread = app.data.number_unread();
+    if unread > 0 {
+        text.push_str(&format!(", {} unread announcement(s) (Tab)", unread));
+    }
+    let unread = app.number_unread_conversations();
+    if unread > 0 {
+        text.push_str(&format!(", {} unread message(s) in the inbox", unread));
+    }
+    Paragraph::new(text)
     .block(
         Block::default()
             .borders(Borders::ALL)
-            .title("Welcome to CanvasTUI"),
+            .title(title)
+            .title_bottom(rate_limit_line),
     )
     .alignment(Alignment::Center)
     .wrap(Wrap { trim: true })
@@ -135,7 +550,27 @@ async fn render_summary(app: Arc<Mutex<App>>) -> Paragraph<'static> {
     let app = app.lock().await;
     let summary = if let Some(i) = app.assignments_state.selected() {
         let assignment = &app.data.assignments[i];
-        let s = format!("Course: {}\nName: {}\n", assignment.course, assignment.name);
+        let mut s = format!("Course: {}\nName: {}\n", assignment.course, assignment.name);
+        if !assignment.custom {
+            s.push_str(&format!("Status: {}", assignment.submission.label()));
+            if let Some(t) = assignment.submitted_at {
+                s.push_str(&format!(", {}", t.with_timezone(&chrono::Local).format("%b %-d %H:%M")));
+            }
+            if let Some(attempt) = assignment.attempt.filter(|a| *a > 0) {
+                s.push_str(&format!(" (attempt {})", attempt));
+            }
+            s.push('\n');
+        }
+        if let Some(score) = assignment.score_text() {
+            s.push_str(&format!("Score: {}", score));
+            if let Some(grade) = &assignment.grade {
+                s.push_str(&format!(" ({})", grade));
+            }
+            s.push('\n');
+        }
+        if let Some(grading_type) = &assignment.grading_type {
+            s.push_str(&format!("Graded as: {}\n", grading_type.replace('_', " ")));
+        }
         s
     } else {
         "No assignment selected".to_string()
@@ -157,33 +592,553 @@ async fn render_links(app: Arc<Mutex<App>>) -> List<'static> {
             ListItem::new(link.title.clone())
         }).collect::<Vec<_>>();
         let selected_style = Style::default().fg(Color::LightGreen).add_modifier(Modifier::BOLD);
-            return List::new(links)
-                .block(
-                    Block::default()
-                    .borders(Borders::ALL)
-                    .title("Links")
-                )
-                .highlight_style(selected_style);
+        List::new(links)
+            .block(
+                Block::default()
+                .borders(Borders::ALL)
+                .title("Links")
+            )
+            .highlight_style(selected_style)
     } else {
-        return List::new::<Vec<String>>(vec![])
+        List::new::<Vec<String>>(vec![])
             .block(
                 Block::default()
                 .borders(Borders::ALL)
                 .title("No Links Found")
-            );
+            )
+    }
+}
+
+async fn render_announcements(app: Arc<Mutex<App>>) -> (Table<'static>, Paragraph<'static>, List<'static>) {
+    let app = app.lock().await;
+    let bold = Style::default()
+        .fg(Color::White)
+        .add_modifier(Modifier::BOLD);
+    let header_cells = ["Course", "Title", "Posted"].iter().map(|h| Cell::from(*h));
+    let header = Row::new(header_cells).style(bold).height(1);
+    let rows = app.data.announcements.iter().map(|a| {
+        let posted = a
+            .posted_at
+            .map_or(String::new(), |t| t.with_timezone(&chrono::Local).format("%b %-d").to_string());
+        let (title, style) = if a.read {
+            (a.title.clone(), Style::default())
+        } else {
+            (format!("● {}", a.title), Style::default().fg(Color::LightCyan).add_modifier(Modifier::BOLD))
+        };
+        Row::new(vec![Cell::from(app.data.course_name(a.course_id)), Cell::from(title), Cell::from(posted)]).style(style)
+    }).collect::<Vec<_>>();
+    let table = Table::default()
+        .rows(rows)
+        .header(header)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::LightGreen))
+                .title(format!("Announcements, {} unread (Tab: discussions, Esc: back)", app.data.number_unread())),
+        )
+        .row_highlight_style(Style::default().fg(Color::LightGreen).add_modifier(Modifier::BOLD))
+        .widths([Constraint::Ratio(3, 10), Constraint::Ratio(5, 10), Constraint::Ratio(2, 10)]);
+
+    let (body, links) = match app.selected_announcement() {
+        Some(a) => {
+            let mut lines = vec![Line::styled(a.title.clone(), bold)];
+            let mut byline = a.author.clone().unwrap_or_default();
+            if let Some(t) = a.posted_at {
+                byline.push_str(&format!(", {}", t.with_timezone(&chrono::Local).format("%b %-d %H:%M")));
+            }
+            lines.push(Line::styled(byline, Style::default().fg(Color::DarkGray)));
+            lines.push(Line::default());
+            lines.extend(a.text().lines().map(|l| Line::from(l.to_string())));
+            let links = a.links.iter().map(|l| ListItem::new(l.title.clone())).collect::<Vec<_>>();
+            (lines, links)
+        }
+        None => (vec![Line::from("No announcements")], vec![]),
+    };
+    let body = Paragraph::new(body)
+        .block(Block::default().borders(Borders::ALL).title("Message (o: open, Ctrl-d/u: scroll)"))
+        .wrap(Wrap { trim: false })
+        .scroll((app.announcement_scroll, 0));
+    let links = List::new(links)
+        .block(Block::default().borders(Borders::ALL).title("Links (J/K, O: open)"))
+        .highlight_style(Style::default().fg(Color::LightGreen).add_modifier(Modifier::BOLD));
+    (table, body, links)
+}
+
+async fn render_discussions(app: Arc<Mutex<App>>) -> (Table<'static>, Paragraph<'static>) {
+    let app = app.lock().await;
+    let bold = Style::default()
+        .fg(Color::White)
+        .add_modifier(Modifier::BOLD);
+    let header_cells = ["Course", "Title", "Replies", "Graded"].iter().map(|h| Cell::from(*h));
+    let header = Row::new(header_cells).style(bold).height(1);
+    let discussions = app.discussions.as_deref().unwrap_or_default();
+    let rows = discussions.iter().map(|d| {
+        let title = if d.locked {
+            format!("🔒{}", d.title)
+        } else {
+            d.title.clone()
+        };
+        let (replies, style) = if d.unread > 0 {
+            (format!("{} ({} new)", d.replies, d.unread), Style::default().fg(Color::LightCyan).add_modifier(Modifier::BOLD))
+        } else {
+            (d.replies.to_string(), Style::default())
+        };
+        // Graded discussions show their assignment's due date and status
+        let graded = match (&d.assignment_id, app.discussion_assignment(d)) {
+            (_, Some(a)) => {
+                let (icon, color) = submission_style(a.submission);
+                let due = a.date.map_or("No due date".to_string(), |t| t.format("%b %-d").to_string());
+                Cell::from(format!("{} {}", icon, due)).style(Style::default().fg(color))
+            }
+            (Some(_), None) => Cell::from("Yes"),
+            (None, None) => Cell::from(""),
+        };
+        Row::new(vec![
+            Cell::from(app.data.course_name(d.course_id)),
+            Cell::from(title),
+            Cell::from(replies),
+            graded,
+        ])
+        .style(style)
+    }).collect::<Vec<_>>();
+    let unread = discussions.iter().map(|d| d.unread).sum::<i64>();
+    let title = match &app.discussions {
+        Some(_) => format!("Discussions, {} unread replies (Enter: open, r: reload, Tab: inbox, Esc: back)", unread),
+        None => "Discussions (loading)".to_string(),
+    };
+    let table = Table::default()
+        .rows(rows)
+        .header(header)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::LightGreen))
+                .title(title),
+        )
+        .row_highlight_style(Style::default().fg(Color::LightGreen).add_modifier(Modifier::BOLD))
+        .widths([
+            Constraint::Ratio(3, 12),
+            Constraint::Ratio(5, 12),
+            Constraint::Ratio(2, 12),
+            Constraint::Ratio(2, 12),
+        ]);
+
+    let body = match app.selected_discussion() {
+        Some(d) => {
+            let mut lines = vec![Line::styled(d.title.clone(), bold)];
+            let mut byline = d.author.clone().unwrap_or_default();
+            if let Some(t) = d.posted_at {
+                byline.push_str(&format!(", {}", t.with_timezone(&chrono::Local).format("%b %-d %H:%M")));
+            }
+            lines.push(Line::styled(byline, Style::default().fg(Color::DarkGray)));
+            lines.push(Line::default());
+            lines.extend(d.text().lines().map(|l| Line::from(l.to_string())));
+            lines
+        }
+        None => vec![Line::from("No discussions")],
+    };
+    let body = Paragraph::new(body)
+        .block(Block::default().borders(Borders::ALL).title("Topic (o: open)"))
+        .wrap(Wrap { trim: false });
+    (table, body)
+}
+
+// The open topic above its replies, each indented under the one it answers
+async fn render_thread(app: Arc<Mutex<App>>) -> (Paragraph<'static>, List<'static>) {
+    let app = app.lock().await;
+    let topic = app
+        .thread_topic
+        .as_ref()
+        .and_then(|id| app.discussions.as_ref()?.iter().find(|d| &d.id == id));
+    let mut lines = vec![];
+    if let Some(d) = topic {
+        lines.push(Line::styled(d.title.clone(), Style::default().fg(Color::White).add_modifier(Modifier::BOLD)));
+        lines.push(Line::styled(d.author.clone().unwrap_or_default(), Style::default().fg(Color::DarkGray)));
+        lines.push(Line::default());
+        lines.extend(d.text().lines().map(|l| Line::from(l.to_string())));
+    }
+    let topic = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).border_style(Style::default().fg(Color::LightGreen)).title("Topic (Esc: back, o: open)"))
+        .wrap(Wrap { trim: false });
+
+    let (items, title) = match &app.thread {
+        Some(thread) => {
+            let items = thread.iter().map(|(depth, e)| {
+                let indent = "  ".repeat(*depth);
+                let mut header = format!("{}{}", indent, e.author.clone().unwrap_or_else(|| "(Unknown)".to_string()));
+                if let Some(t) = e.created_at {
+                    header.push_str(&format!(", {}", t.with_timezone(&chrono::Local).format("%b %-d %H:%M")));
+                }
+                let style = if e.read {
+                    Style::default().fg(Color::DarkGray)
+                } else {
+                    header.push_str(" ●");
+                    Style::default().fg(Color::LightCyan).add_modifier(Modifier::BOLD)
+                };
+                let mut lines = vec![Line::styled(header, style)];
+                lines.extend(e.text().lines().map(|l| Line::from(format!("{}{}", indent, l))));
+                lines.push(Line::default());
+                ListItem::new(lines)
+            }).collect::<Vec<_>>();
+            let unread = thread.iter().filter(|(_, e)| !e.read).count();
+            (items, format!("Replies, {} unread (r/R: reply to entry/topic, m/M: mark read/all read)", unread))
+        }
+        None => (vec![], "Replies (loading)".to_string()),
+    };
+    let replies = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    (topic, replies)
+}
+
+async fn render_inbox(app: Arc<Mutex<App>>) -> Table<'static> {
+    let app = app.lock().await;
+    let bold = Style::default()
+        .fg(Color::White)
+        .add_modifier(Modifier::BOLD);
+    let header_cells = ["Subject", "With", "Course", "Updated"].iter().map(|h| Cell::from(*h));
+    let header = Row::new(header_cells).style(bold).height(1);
+    let user_id = app.user_id.clone().unwrap_or_default();
+    let conversations = app.conversations.as_deref().unwrap_or_default();
+    let rows = conversations.iter().map(|c| {
+        let updated = c
+            .updated_at
+            .map_or(String::new(), |t| t.with_timezone(&chrono::Local).format("%b %-d %H:%M").to_string());
+        let (subject, style) = if c.unread {
+            (format!("● {}", c.subject), Style::default().fg(Color::LightCyan).add_modifier(Modifier::BOLD))
+        } else {
+            (c.subject.clone(), Style::default())
+        };
+        Row::new(vec![
+            Cell::from(subject),
+            Cell::from(c.others(&user_id)),
+            Cell::from(c.context_name.clone().unwrap_or_default()),
+            Cell::from(updated),
+        ])
+        .style(style)
+    }).collect::<Vec<_>>();
+    let title = match &app.conversations {
+        Some(_) => format!(
+            "Inbox, {} unread (Enter: open, n: new message, r: reload, Tab/Esc: back)",
+            app.number_unread_conversations()
+        ),
+        None => "Inbox (loading)".to_string(),
+    };
+    Table::default()
+        .rows(rows)
+        .header(header)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::LightGreen))
+                .title(title),
+        )
+        .row_highlight_style(Style::default().fg(Color::LightGreen).add_modifier(Modifier::BOLD))
+        .widths([
+            Constraint::Ratio(4, 10),
+            Constraint::Ratio(3, 10),
+            Constraint::Ratio(2, 10),
+            Constraint::Ratio(1, 10),
+        ])
+}
+
+async fn render_conversation(app: Arc<Mutex<App>>) -> Paragraph<'static> {
+    let app = app.lock().await;
+    let conversation = app
+        .open_conversation
+        .as_ref()
+        .and_then(|id| app.conversations.as_ref()?.iter().find(|c| &c.id == id));
+    let subject = conversation.map_or(String::new(), |c| c.subject.clone());
+    let lines = match &app.messages {
+        Some(messages) => {
+            let mut lines = vec![];
+            for m in messages {
+                let mut header = m.author.clone().unwrap_or_else(|| "(Unknown)".to_string());
+                if let Some(t) = m.created_at {
+                    header.push_str(&format!(", {}", t.with_timezone(&chrono::Local).format("%b %-d %H:%M")));
+                }
+                lines.push(Line::styled(header, Style::default().fg(Color::LightGreen).add_modifier(Modifier::BOLD)));
+                lines.extend(m.body.lines().map(|l| Line::from(l.to_string())));
+                lines.push(Line::default());
+            }
+            lines
+        }
+        None => vec![Line::from("Loading...")],
+    };
+    Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::LightGreen))
+                .title(format!("{} (r: reply, j/k: scroll, Esc: back)", subject)),
+        )
+        .wrap(Wrap { trim: false })
+        .scroll((app.message_scroll, 0))
+}
+
+// Course and recipients of a new message above the suggestions for the search typed
+async fn render_new_message(app: Arc<Mutex<App>>) -> (Paragraph<'static>, List<'static>, ListState) {
+    let app = app.lock().await;
+    let active = Style::default().fg(Color::LightYellow).add_modifier(Modifier::BOLD);
+    let (course_style, recipients_style) = match app.mode {
+        Mode::NewMessage(MessageField::Course) => (active, Style::default()),
+        _ => (Style::default(), active),
+    };
+    let course = app
+        .new_message_course()
+        .map_or(String::new(), |id| app.data.course_name(id));
+    let message = app.new_message.as_ref();
+    let mut recipients = message
+        .map(|m| m.recipients.iter().map(|r| r.name.clone()).collect::<Vec<String>>())
+        .unwrap_or_default()
+        .join(", ");
+    if !recipients.is_empty() {
+        recipients.push_str(", ");
+    }
+    let input = message.map_or(String::new(), |m| m.input.clone());
+    let fields = Paragraph::new(vec![
+        Line::from(vec![Span::styled("Course: ", course_style), Span::raw(format!("◂ {} ▸", course))]),
+        Line::from(vec![
+            Span::styled("To: ", recipients_style),
+            Span::raw(recipients),
+            Span::styled(format!("{}▏", input), Style::default().fg(Color::White)),
+        ]),
+    ])
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::LightYellow))
+            .title("New message (Tab: switch field, Enter: add recipient, Enter on an empty search: write, Esc: cancel)"),
+    );
+
+    let loaded = app.new_message_course().is_some_and(|id| app.recipients.contains_key(&id));
+    let items = app
+        .recipient_suggestions()
+        .into_iter()
+        .map(|r| {
+            if r.instructor {
+                ListItem::new(Line::from(vec![
+                    Span::raw(r.name.clone()),
+                    Span::styled(" (instructor)", Style::default().fg(Color::LightCyan)),
+                ]))
+            } else {
+                ListItem::new(r.name.clone())
+            }
+        })
+        .collect::<Vec<_>>();
+    let title = if loaded { "Suggestions (↑/↓)" } else { "Suggestions (loading)" };
+    let suggestions = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(Style::default().fg(Color::LightGreen).add_modifier(Modifier::BOLD));
+    let state = ListState::default().with_selected(message.map(|m| m.suggestion));
+    (fields, suggestions, state)
+}
+
+async fn render_status(app: Arc<Mutex<App>>) -> Paragraph<'static> {
+    let app = app.lock().await;
+    let status = &app.status;
+    let mut spans = vec![];
+    if status.is_refreshing() {
+        spans.push(Span::styled(
+            format!(" {} Refreshing ", status.spinner()),
+            Style::default().fg(Color::LightYellow),
+        ));
+    }
+    let last_sync = match status.last_sync {
+        Some(t) => format!(" Last sync: {} ", t.format("%a %H:%M")),
+        None => " Not synced yet ".to_string(),
+    };
+    spans.push(Span::styled(last_sync, Style::default().fg(Color::DarkGray)));
+    if let Some(e) = &status.last_error {
+        spans.push(Span::styled(format!(" {} ", e), Style::default().fg(Color::Red)));
     }
+    spans.push(Span::styled(" (L: log) ", Style::default().fg(Color::DarkGray)));
+    Paragraph::new(Line::from(spans))
+}
+
+async fn render_log(app: Arc<Mutex<App>>) -> List<'static> {
+    let app = app.lock().await;
+    let items = app.status.log.iter().map(|entry| {
+        let (label, color) = match entry.level {
+            LogLevel::Info => ("INFO ", Color::Gray),
+            LogLevel::Warning => ("WARN ", Color::Yellow),
+            LogLevel::Error => ("ERROR", Color::Red),
+        };
+        ListItem::new(Line::from(vec![
+            Span::styled(format!("{} ", entry.time.format("%H:%M:%S")), Style::default().fg(Color::DarkGray)),
+            Span::styled(format!("{} ", label), Style::default().fg(color)),
+            Span::raw(entry.message.clone()),
+        ]))
+    }).collect::<Vec<_>>();
+    List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Log ({} entries, j/k: scroll, L/Esc: close)", app.status.log.len())),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+}
+
+async fn render_archive_groups(app: Arc<Mutex<App>>) -> List<'static> {
+    let app = app.lock().await;
+    let items = app.archive_groups().into_iter().map(|(term, course)| {
+        ListItem::new(Line::from(vec![
+            Span::raw(course),
+            Span::styled(format!("  {}", term), Style::default().fg(Color::DarkGray)),
+        ]))
+    }).collect::<Vec<_>>();
+    List::new(items)
+        .block(Block::default().borders(Borders::ALL).title("Courses (h/l)"))
+        .highlight_style(Style::default().fg(Color::LightGreen).add_modifier(Modifier::BOLD))
+}
+
+async fn render_archive(app: Arc<Mutex<App>>) -> Table<'static> {
+    let app = app.lock().await;
+    let bold = Style::default()
+        .fg(Color::White)
+        .add_modifier(Modifier::BOLD);
+    let header_cells = ["Name", "Due Date", "Status", "Score"].iter().map(|h| Cell::from(*h));
+    let header = Row::new(header_cells).style(bold).height(1);
+    let rows = app.archive_selection().into_iter().map(|a| {
+        let date = match a.date {
+            Some(date) => date.format("%b %-d %Y, %H:%M").to_string(),
+            None => "(No due date)".to_string(),
+        };
+        let score = a.score_text().unwrap_or("-".to_string());
+        Row::new(vec![
+            Cell::from(a.name.clone()),
+            Cell::from(date),
+            submission_cell(a),
+            Cell::from(score),
+        ])
+    }).collect::<Vec<_>>();
+
+    Table::default()
+        .rows(rows)
+        .header(header)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Archive ({} assignments, j/k: scroll, o: open, a/Esc: close)", app.data.archive.len())),
+        )
+        .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .widths([
+            Constraint::Ratio(5, 10),
+            Constraint::Ratio(2, 10),
+            Constraint::Ratio(2, 10),
+            Constraint::Ratio(1, 10),
+        ])
+}
+
+// Rectangle of the given percentage size centered in `area`
+fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(area);
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
 }
 
 async fn render_default<B: Backend>(terminal: &mut Terminal<B>, app: Arc<Mutex<App>>) {
     let welcome = render_welcome(app.clone()).await;
-    let assignments = render_assignments(app.clone()).await;
-    let mut assignments_state = app.lock().await.assignments_state.clone();
+    let overdue = render_assignments(app.clone(), true).await;
+    let assignments = render_assignments(app.clone(), false).await;
+    let n_overdue = app.lock().await.data.number_overdue();
+
+    // The selection indexes the whole list, split it between the two sections
+    let selected = app.lock().await.assignments_state.selected();
+    let mut overdue_state = TableState::default()
+        .with_selected(selected.filter(|i| *i < n_overdue));
+    let mut assignments_state = TableState::default()
+        .with_selected(selected.and_then(|i| i.checked_sub(n_overdue)));
     let summary = render_summary(app.clone()).await;
     let links = render_links(app.clone()).await;
     let mut links_state = app.lock().await.links_state.clone();
     let grades = render_grades(app.clone()).await;
+    let mut grades_state = app.lock().await.grades_state.clone();
+    let breakdown = if matches!(app.lock().await.mode, Mode::Breakdown | Mode::WhatIf(_)) {
+        Some(render_breakdown(app.clone()).await)
+    } else {
+        None
+    };
+    let what_if = if matches!(app.lock().await.mode, Mode::WhatIf(_)) {
+        Some(render_what_if(app.clone()).await)
+    } else {
+        None
+    };
+    let mut what_if_state = app.lock().await.what_if_state.clone();
+    let announcements = if app.lock().await.mode == Mode::Announcements {
+        Some(render_announcements(app.clone()).await)
+    } else {
+        None
+    };
+    let mut announcements_state = app.lock().await.announcements_state.clone();
+    let mut announcement_links_state = app.lock().await.announcement_links_state.clone();
+    let discussions = if app.lock().await.mode == Mode::Discussions {
+        Some(render_discussions(app.clone()).await)
+    } else {
+        None
+    };
+    let mut discussions_state = app.lock().await.discussions_state.clone();
+    let thread = if app.lock().await.mode == Mode::Thread {
+        Some(render_thread(app.clone()).await)
+    } else {
+        None
+    };
+    let mut thread_state = app.lock().await.thread_state.clone();
+    let inbox = if matches!(app.lock().await.mode, Mode::Inbox | Mode::NewMessage(_)) {
+        Some(render_inbox(app.clone()).await)
+    } else {
+        None
+    };
+    let mut conversations_state = app.lock().await.conversations_state.clone();
+    let conversation = if app.lock().await.mode == Mode::Conversation {
+        Some(render_conversation(app.clone()).await)
+    } else {
+        None
+    };
+    let new_message = if matches!(app.lock().await.mode, Mode::NewMessage(_)) {
+        Some(render_new_message(app.clone()).await)
+    } else {
+        None
+    };
+    let term = if app.lock().await.mode == Mode::Term {
+        Some(render_term(app.clone()).await)
+    } else {
+        None
+    };
+    let status = render_status(app.clone()).await;
+    let log = if app.lock().await.mode == Mode::Log {
+        Some(render_log(app.clone()).await)
+    } else {
+        None
+    };
+    let mut log_state = app.lock().await.log_state.clone();
+    let archive = if app.lock().await.mode == Mode::Archive {
+        Some((render_archive_groups(app.clone()).await, render_archive(app.clone()).await))
+    } else {
+        None
+    };
+    let mut archive_groups_state = app.lock().await.archive_groups_state.clone();
+    let mut archive_state = app.lock().await.archive_state.clone();
 
     let _ = terminal.draw(|f| {
+        let outer = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(1)])
+            .split(f.area());
+
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints(
@@ -194,7 +1149,7 @@ async fn render_default<B: Backend>(terminal: &mut Terminal<B>, app: Arc<Mutex<A
                 ]
                 .as_ref(),
             )
-            .split(f.area());
+            .split(outer[0]);
 
         let bottom_chunks = Layout::default()
             .direction(Direction::Horizontal)
@@ -202,14 +1157,132 @@ async fn render_default<B: Backend>(terminal: &mut Terminal<B>, app: Arc<Mutex<A
             .split(chunks[2]);
 
         f.render_widget(welcome, chunks[0]);
-        f.render_stateful_widget(assignments, chunks[1], &mut assignments_state);
+        if n_overdue > 0 {
+            let sections = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(n_overdue.min(5) as u16 + 3), Constraint::Min(0)])
+                .split(chunks[1]);
+            f.render_stateful_widget(overdue, sections[0], &mut overdue_state);
+            f.render_stateful_widget(assignments, sections[1], &mut assignments_state);
+        } else {
+            f.render_stateful_widget(assignments, chunks[1], &mut assignments_state);
+        }
         f.render_widget(summary, bottom_chunks[0]);
         f.render_stateful_widget(links, bottom_chunks[1], &mut links_state);
-        f.render_widget(grades, bottom_chunks[2]);
+        f.render_stateful_widget(grades, bottom_chunks[2], &mut grades_state);
+        f.render_widget(status, outer[1]);
+
+        if let Some(log) = log {
+            let area = centered_rect(80, 70, f.area());
+            f.render_widget(Clear, area);
+            f.render_stateful_widget(log, area, &mut log_state);
+        }
+
+        if let Some(breakdown) = breakdown {
+            if let Some((what_if, solution)) = what_if {
+                let area = centered_rect(70, 80, f.area());
+                let chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Ratio(1, 2), Constraint::Min(0), Constraint::Length(3)])
+                    .split(area);
+                f.render_widget(Clear, area);
+                f.render_widget(breakdown, chunks[0]);
+                f.render_stateful_widget(what_if, chunks[1], &mut what_if_state);
+                f.render_widget(solution, chunks[2]);
+            } else {
+                let area = centered_rect(70, 50, f.area());
+                f.render_widget(Clear, area);
+                f.render_widget(breakdown, area);
+            }
+        }
+
+        // The announcements tab covers everything but the header and the status line
+        if let Some((list, body, links)) = announcements {
+            let area = chunks[1].union(chunks[2]);
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Ratio(2, 5), Constraint::Ratio(3, 5)])
+                .split(area);
+            let right = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(0), Constraint::Length(7)])
+                .split(columns[1]);
+            f.render_widget(Clear, area);
+            f.render_stateful_widget(list, columns[0], &mut announcements_state);
+            f.render_widget(body, right[0]);
+            f.render_stateful_widget(links, right[1], &mut announcement_links_state);
+        }
+
+        if let Some((list, body)) = discussions {
+            let area = chunks[1].union(chunks[2]);
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Ratio(3, 5), Constraint::Ratio(2, 5)])
+                .split(area);
+            f.render_widget(Clear, area);
+            f.render_stateful_widget(list, columns[0], &mut discussions_state);
+            f.render_widget(body, columns[1]);
+        }
+
+        if let Some((topic, replies)) = thread {
+            let area = chunks[1].union(chunks[2]);
+            let rows = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Ratio(1, 3), Constraint::Ratio(2, 3)])
+                .split(area);
+            f.render_widget(Clear, area);
+            f.render_widget(topic, rows[0]);
+            f.render_stateful_widget(replies, rows[1], &mut thread_state);
+        }
+
+        if let Some(inbox) = inbox {
+            let area = chunks[1].union(chunks[2]);
+            f.render_widget(Clear, area);
+            f.render_stateful_widget(inbox, area, &mut conversations_state);
+        }
+
+        if let Some(conversation) = conversation {
+            let area = chunks[1].union(chunks[2]);
+            f.render_widget(Clear, area);
+            f.render_widget(conversation, area);
+        }
+
+        if let Some((fields, suggestions, mut suggestions_state)) = new_message {
+            let area = centered_rect(70, 60, f.area());
+            let rows = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(4), Constraint::Min(0)])
+                .split(area);
+            f.render_widget(Clear, area);
+            f.render_widget(fields, rows[0]);
+            f.render_stateful_widget(suggestions, rows[1], &mut suggestions_state);
+        }
+
+        if let Some((courses, gpa)) = term {
+            let area = centered_rect(70, 60, f.area());
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(0), Constraint::Length(6)])
+                .split(area);
+            f.render_widget(Clear, area);
+            f.render_widget(courses, chunks[0]);
+            f.render_widget(gpa, chunks[1]);
+        }
+
+        if let Some((groups, archive)) = archive {
+            let area = centered_rect(90, 80, f.area());
+            let chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Ratio(1, 3), Constraint::Ratio(2, 3)])
+                .split(area);
+            f.render_widget(Clear, area);
+            f.render_stateful_widget(groups, chunks[0], &mut archive_groups_state);
+            f.render_stateful_widget(archive, chunks[1], &mut archive_state);
+        }
     });
 }
 
-pub async fn run(data_path: String, course_ids: Vec<u32>, data: Data,) -> Result<(), Box<dyn Error>> {
+pub async fn run(storage: Box<dyn Storage>, profiles: Vec<Profile>, profile_i: usize, client: CanvasClient, settings: Settings, data: Data, load_error: Option<String>) -> Result<(), Box<dyn Error>> {
     // Setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -219,14 +1292,24 @@ pub async fn run(data_path: String, course_ids: Vec<u32>, data: Data,) -> Result
 
     // Create app and run it
     let app = Arc::new(Mutex::new(App::new(
-        data_path,
-        Duration::from_millis(1000),
-        course_ids,
+        storage,
+        Duration::from_millis(250),
+        profiles,
+        profile_i,
+        client,
+        settings,
         data,
     )));
+    if let Some(e) = load_error {
+        app.lock().await.status.error(e);
+    }
 
     // Initial refresh
     app::refresh(app.clone()).await?;
+    // The inbox isn't part of a refresh, but messages shouldn't wait for the tab to be opened
+    if !app.lock().await.settings.offline {
+        app::load_conversations(app.clone()).await;
+    }
 
     // Main loop and tick logic
     let mut last_tick = Instant::now();
@@ -240,9 +1323,16 @@ pub async fn run(data_path: String, course_ids: Vec<u32>, data: Data,) -> Result
             .tick_rate
             .checked_sub(last_tick.elapsed())
             .unwrap_or(Duration::from_secs(0));
-        if event::poll(timeout)? {
-            if handle_input(Arc::clone(&app)).await? {
-                break;
+        if event::poll(timeout)? && handle_input(Arc::clone(&app)).await? {
+            break;
+        }
+        // Replies are written in an external editor, which needs the terminal to itself
+        let compose = app.lock().await.compose.take();
+        if let Some(compose) = compose {
+            match editor::compose(&mut terminal, compose.template()) {
+                Ok(Some(text)) => app::send_composed(app.clone(), compose, text).await,
+                Ok(None) => app.lock().await.status.info("Nothing was written, not sending".to_string()),
+                Err(e) => app.lock().await.status.error(format!("Error running the editor: {}", e)),
             }
         }
         if last_tick.elapsed() >= app.lock().await.tick_rate {
@@ -0,0 +1,132 @@
+use crate::types::course::Course;
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind},
+    execute,
+    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+};
+use ratatui::{
+    Terminal,
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+};
+use std::{error::Error, io};
+
+// Let the user choose which courses to show. Returns None if the picker was cancelled or
+// nothing was selected
+pub fn pick_courses(courses: &[Course]) -> Result<Option<Vec<u32>>, Box<dyn Error>> {
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+
+    let res = run_picker(&mut terminal, courses);
+
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    res
+}
+
+fn run_picker(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    courses: &[Course],
+) -> Result<Option<Vec<u32>>, Box<dyn Error>> {
+    let mut checked = vec![true; courses.len()];
+    let mut state = ListState::default();
+    if !courses.is_empty() {
+        state.select(Some(0));
+    }
+
+    loop {
+        let items = courses
+            .iter()
+            .zip(checked.iter())
+            .map(|(c, checked)| {
+                let mark = if *checked { "[x]" } else { "[ ]" };
+                ListItem::new(format!("{} {}", mark, c))
+            })
+            .collect::<Vec<_>>();
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Select your courses"),
+            )
+            .highlight_style(Style::default().fg(Color::LightGreen).add_modifier(Modifier::BOLD));
+        let help = Paragraph::new(
+            "j/k: move  space: toggle  a: toggle all  enter: save (nothing checked: skip)  q/esc: skip",
+        )
+        .block(Block::default().borders(Borders::ALL));
+
+        terminal.draw(|f| {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(3), Constraint::Length(3)])
+                .split(f.area());
+            f.render_stateful_widget(list, chunks[0], &mut state);
+            f.render_widget(help, chunks[1]);
+        })?;
+
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('j') | KeyCode::Down => state.select_next(),
+                KeyCode::Char('k') | KeyCode::Up => state.select_previous(),
+                KeyCode::Char(' ') => {
+                    if let Some(i) = state.selected()
+                        && i < checked.len()
+                    {
+                        checked[i] = !checked[i];
+                    }
+                }
+                KeyCode::Char('a') => {
+                    let all = checked.iter().all(|c| *c);
+                    checked.iter_mut().for_each(|c| *c = !all);
+                }
+                KeyCode::Enter => return Ok(selected_ids(courses, &checked)),
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(None),
+                _ => (),
+            }
+        }
+    }
+}
+
+// Ids of the checked courses. An empty selection would save `course_ids = []` and hide every
+// course for good, so it counts as skipping the picker
+fn selected_ids(courses: &[Course], checked: &[bool]) -> Option<Vec<u32>> {
+    let ids = courses
+        .iter()
+        .zip(checked.iter())
+        .filter(|(_, checked)| **checked)
+        .map(|(c, _)| c.id)
+        .collect::<Vec<u32>>();
+    if ids.is_empty() { None } else { Some(ids) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn course(id: u32) -> Course {
+        Course {
+            id,
+            name: format!("Course {}", id),
+            nickname: None,
+            term: None,
+            start_at: None,
+            end_at: None,
+        }
+    }
+
+    #[test]
+    fn empty_selection_is_skipped() {
+        let courses = vec![course(1), course(2)];
+        assert_eq!(selected_ids(&courses, &[true, false]), Some(vec![1]));
+        assert_eq!(selected_ids(&courses, &[false, false]), None);
+        assert_eq!(selected_ids(&[], &[]), None);
+    }
+}
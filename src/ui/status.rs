@@ -0,0 +1,95 @@
+use chrono::{DateTime, Local};
+use std::time::Instant;
+
+const SPINNER: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+// Oldest entries are dropped past this many
+const MAX_LOG_ENTRIES: usize = 500;
+
+#[derive(PartialEq, Eq, Copy, Clone)]
+pub enum LogLevel {
+    Info,
+    Warning,
+    Error,
+}
+
+pub struct LogEntry {
+    pub time: DateTime<Local>,
+    pub level: LogLevel,
+    pub message: String,
+}
+
+/// Refresh progress, last sync and the history of warnings and errors shown in the TUI
+pub struct Status {
+    /// Set while a refresh is running, identifies it so that a stale one can't report back
+    pub refresh_started: Option<Instant>,
+    pub last_sync: Option<DateTime<Local>>,
+    pub last_error: Option<String>,
+    pub log: Vec<LogEntry>,
+    spinner_i: usize,
+}
+
+impl Status {
+    pub fn new() -> Self {
+        Self {
+            refresh_started: None,
+            last_sync: None,
+            last_error: None,
+            log: vec![],
+            spinner_i: 0,
+        }
+    }
+
+    pub fn is_refreshing(&self) -> bool {
+        self.refresh_started.is_some()
+    }
+
+    pub fn start_refresh(&mut self) -> Instant {
+        let started = Instant::now();
+        self.refresh_started = Some(started);
+        started
+    }
+
+    // Mark the refresh finished, a refresh without any error counts as a successful sync
+    pub fn finish_refresh(&mut self, errors: usize) {
+        self.refresh_started = None;
+        if errors == 0 {
+            self.last_sync = Some(Local::now());
+            self.last_error = None;
+        }
+    }
+
+    pub fn spinner(&self) -> &'static str {
+        SPINNER[self.spinner_i % SPINNER.len()]
+    }
+
+    pub fn on_tick(&mut self) {
+        if self.is_refreshing() {
+            self.spinner_i = self.spinner_i.wrapping_add(1);
+        }
+    }
+
+    pub fn info(&mut self, message: String) {
+        self.push(LogLevel::Info, message);
+    }
+
+    pub fn warn(&mut self, message: String) {
+        self.push(LogLevel::Warning, message);
+    }
+
+    pub fn error(&mut self, message: String) {
+        self.last_error = Some(message.clone());
+        self.push(LogLevel::Error, message);
+    }
+
+    fn push(&mut self, level: LogLevel, message: String) {
+        self.log.push(LogEntry {
+            time: Local::now(),
+            level,
+            message,
+        });
+        if self.log.len() > MAX_LOG_ENTRIES {
+            self.log.remove(0);
+        }
+    }
+}